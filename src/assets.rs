@@ -17,3 +17,7 @@ pub struct PowerBuffMaterial(pub Handle<ColorMaterial>);
 pub struct SpeedBuffMaterial(pub Handle<ColorMaterial>);
 
 pub struct BombNumberBuffMaterial(pub Handle<ColorMaterial>);
+
+pub struct WallPassBuffMaterial(pub Handle<ColorMaterial>);
+
+pub struct BombPassBuffMaterial(pub Handle<ColorMaterial>);
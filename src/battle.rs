@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+
+use crate::{
+    assets::FloorOrWallTextureAtlas,
+    components::{Blast, Bomb, Buff, Destructible, Fire, Lives, Player, Seat, Stop},
+    creatures::Creature,
+    entities::create_normal_wall,
+    events::{DeathCause, GameEvents},
+    grid::{Grid, Tile},
+    player::Death,
+    resources::Map,
+    state::{GameState, RunState},
+};
+
+pub trait BattleSystems {
+    fn battle_systems(&mut self) -> &mut Self;
+}
+impl BattleSystems for SystemStage {
+    fn battle_systems(&mut self) -> &mut Self {
+        self.add_system(sudden_death.system())
+            .add_system(decide_round.system())
+            .add_system(finish_round.system())
+    }
+}
+
+// Round wins it takes to win a versus match.
+pub const ROUNDS_TO_WIN: u32 = 3;
+// Seconds into a round before the walls start closing in, then one more every `WALL_DROP_TIME`.
+const SUDDEN_DEATH_TIME: f32 = 90.0;
+const WALL_DROP_TIME: f32 = 0.5;
+
+// The bomber who took the round, held still until the results come up.
+pub struct RoundOver;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Won(usize),
+    Draw,
+}
+
+// A versus match so far, kept in `RunState` across rounds.
+#[derive(Default)]
+pub struct Battle {
+    pub round: u32,
+    // how the round being played went, once it is decided
+    pub outcome: Option<Outcome>,
+    // round wins by seat
+    wins: Vec<u32>,
+    finished: bool,
+    round_time: f32,
+    // how far along `closing_order` the walls have come
+    closing: usize,
+    walls_dropped: usize,
+}
+
+impl Battle {
+    pub fn reset(&mut self) {
+        *self = Battle::default();
+    }
+    pub fn start_round(&mut self) {
+        self.round += 1;
+        self.outcome = None;
+        self.finished = false;
+        self.round_time = 0.0;
+        self.closing = 0;
+        self.walls_dropped = 0;
+    }
+    pub fn wins(&self, seat: usize) -> u32 {
+        self.wins.get(seat).copied().unwrap_or(0)
+    }
+    // Whoever has won enough rounds, the match is over then.
+    pub fn champion(&self) -> Option<usize> {
+        self.wins.iter().position(|&wins| wins >= ROUNDS_TO_WIN)
+    }
+    fn finish(&mut self) {
+        if let Some(Outcome::Won(seat)) = self.outcome {
+            if self.wins.len() <= seat {
+                self.wins.resize(seat + 1, 0);
+            }
+            self.wins[seat] += 1;
+        }
+        self.finished = true;
+    }
+}
+
+// Every tile spiralling in from the edges, the order the walls close in.
+fn closing_order(width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut order = Vec::new();
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, width - 1, height - 1);
+    while left <= right && top <= bottom {
+        order.extend((left..=right).map(|x| (x, top)));
+        order.extend((top + 1..=bottom).map(|y| (right, y)));
+        if top < bottom {
+            order.extend((left..right).rev().map(|x| (x, bottom)));
+        }
+        if left < right {
+            order.extend((top + 1..bottom).rev().map(|y| (left, y)));
+        }
+        if right == 0 || bottom == 0 {
+            break;
+        }
+        left += 1;
+        top += 1;
+        right -= 1;
+        bottom -= 1;
+    }
+    order
+}
+
+// Once a round drags on, walls close in from the edges and crush whoever is under them.
+fn sudden_death(
+    commands: &mut Commands,
+    time: Res<Time>,
+    map: Res<Map>,
+    grid: Res<Grid>,
+    game_state: Res<State<GameState>>,
+    floor_or_wall_texture_atlas: Res<FloorOrWallTextureAtlas>,
+    mut runstate: ResMut<RunState>,
+    mut game_events: ResMut<Events<GameEvents>>,
+    bomber_query: Query<(Entity, &Transform), With<Seat>>,
+    creature_query: Query<(Entity, &Transform), With<Creature>>,
+    // boxes and items, bombers and creatures are hit instead so they die the usual way
+    clear_query: Query<
+        (Entity, &Transform),
+        (
+            Or<(With<Destructible>, With<Buff>)>,
+            Without<Player>,
+            Without<Creature>,
+        ),
+    >,
+    bomb_query: Query<(Entity, &Transform, &Bomb)>,
+) {
+    let battle = &mut runstate.battle;
+    if !map.rules.last_standing_wins || battle.outcome.is_some() || grid.width() == 0 {
+        return;
+    }
+    // the walls hold still while the game is paused
+    if *game_state.current() != GameState::Game {
+        return;
+    }
+    battle.round_time += time.delta_seconds();
+    let next_drop = SUDDEN_DEATH_TIME + battle.walls_dropped as f32 * WALL_DROP_TIME;
+    if battle.round_time < next_drop {
+        return;
+    }
+    let order = closing_order(grid.width(), grid.height());
+    while let Some(&(x, y)) = order.get(battle.closing) {
+        battle.closing += 1;
+        // the border and the pillars are walls already
+        if grid.get(x, y) == Some(Tile::Wall) {
+            continue;
+        }
+        battle.walls_dropped += 1;
+        let on_tile =
+            |transform: &Transform| grid.to_tile(transform.translation.truncate()) == Some((x, y));
+        for (entity, _) in clear_query
+            .iter()
+            .filter(|(_, transform)| on_tile(transform))
+        {
+            commands.despawn(entity);
+        }
+        for (entity, _, bomb) in bomb_query
+            .iter()
+            .filter(|(_, transform, _)| on_tile(transform))
+        {
+            commands.despawn(entity);
+            game_events.send(GameEvents::RecoveryBombNumber(bomb.player));
+        }
+        for (entity, _) in bomber_query
+            .iter()
+            .filter(|(_, transform)| on_tile(transform))
+        {
+            game_events.send(GameEvents::PlayerHit(entity, DeathCause::Hazard));
+        }
+        create_normal_wall(
+            commands,
+            grid.to_position(x, y),
+            floor_or_wall_texture_atlas.0.clone(),
+        );
+        if let Some(wall) = commands.current_entity() {
+            // the wall itself is the blast, so nobody scores the kill
+            let blast = Blast {
+                bomb: wall,
+                owner: wall,
+            };
+            for (entity, _) in creature_query
+                .iter()
+                .filter(|(_, transform)| on_tile(transform))
+            {
+                game_events.send(GameEvents::CreatureHit(entity, blast));
+            }
+        }
+        break;
+    }
+}
+
+// A bomber is out once it is hit on its last life, the one left standing takes the round.
+// Bombers knocked out by the same blast draw it, so the round waits for the fire to die down.
+fn decide_round(
+    commands: &mut Commands,
+    map: Res<Map>,
+    mut runstate: ResMut<RunState>,
+    query: Query<(Entity, &Seat, &Lives, Option<&Death>)>,
+    fire_query: Query<&Fire>,
+) {
+    let battle = &mut runstate.battle;
+    // the arena is only filled in once the round is set up
+    if !map.rules.last_standing_wins || battle.outcome.is_some() || query.iter().next().is_none() {
+        return;
+    }
+    let mut standing = query
+        .iter()
+        .filter(|(_, _, lives, death)| lives.0 > 1 || (lives.0 == 1 && death.is_none()));
+    let outcome = match (standing.next(), standing.next()) {
+        (None, _) => Outcome::Draw,
+        (Some((winner, seat, _, _)), None) => {
+            // the embers of the blast that knocked the others out may still reach the winner
+            if fire_query.iter().next().is_some() {
+                return;
+            }
+            // nothing can take the round from the winner any more
+            commands.insert(winner, (Stop, RoundOver));
+            Outcome::Won(seat.0)
+        }
+        _ => return,
+    };
+    battle.outcome = Some(outcome);
+}
+
+// The results come up once the death animations have played out.
+fn finish_round(
+    mut runstate: ResMut<RunState>,
+    mut game_events: ResMut<Events<GameEvents>>,
+    death_query: Query<&Death>,
+) {
+    let battle = &mut runstate.battle;
+    if let Some(outcome) = battle.outcome {
+        if !battle.finished && death_query.iter().next().is_none() {
+            battle.finish();
+            game_events.send(GameEvents::RoundOver(outcome));
+        }
+    }
+}
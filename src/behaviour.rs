@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::{
+    components::{Direction, Footprint, Stop},
+    creatures::Creature,
+    grid::{DangerMap, Grid, PlayerDistance},
+    utils::TILE_WIDTH,
+};
+
+// What a creature is doing right now.
+#[derive(Debug, Clone)]
+pub enum Behaviour {
+    // stands still until the timer runs out
+    Idle(Timer),
+    Wander,
+    // walking the personality's route, the index is the next step
+    Patrol(usize),
+    Chase,
+    Flee,
+    // knocked out by a hit
+    Stunned(Timer),
+}
+
+// How a kind of creature behaves, as data for the state machine below.
+#[derive(Debug, Clone, Copy)]
+pub struct Personality {
+    // chance out of 100 to turn off at an intersection while wandering
+    pub turn_probability: i32,
+    // chases players within this walking distance, 0 never chases
+    pub chase_range: u32,
+    // runs out of blast zones and keeps from walking into them
+    pub flees: bool,
+    // chance out of 100 per second to stop for `rest_time` while roaming
+    pub rest_probability: i32,
+    pub rest_time: f32,
+    pub stun_time: f32,
+    // steps walked over and over instead of wandering, blocked steps are skipped
+    pub patrol: &'static [Direction],
+}
+
+// What a creature notices about its tile this frame.
+pub struct Senses {
+    pub player_distance: Option<u32>,
+    pub in_danger: bool,
+}
+
+impl Personality {
+    // The state to fall back to when nothing else is going on.
+    pub fn calm(&self) -> Behaviour {
+        if self.patrol.is_empty() {
+            Behaviour::Wander
+        } else {
+            Behaviour::Patrol(0)
+        }
+    }
+    pub fn stunned(&self) -> Option<Behaviour> {
+        if self.stun_time > 0.0 {
+            Some(Behaviour::Stunned(Timer::from_seconds(
+                self.stun_time,
+                false,
+            )))
+        } else {
+            None
+        }
+    }
+    fn sees_player(&self, senses: &Senses) -> bool {
+        self.chase_range > 0
+            && senses
+                .player_distance
+                .map_or(false, |distance| distance <= self.chase_range)
+    }
+    // The transition rules, `None` keeps the current state.
+    pub fn next(&self, current: &Behaviour, senses: &Senses, delta: f32) -> Option<Behaviour> {
+        match current {
+            Behaviour::Stunned(timer) => {
+                if timer.finished() {
+                    Some(self.calm())
+                } else {
+                    None
+                }
+            }
+            _ if self.flees && senses.in_danger => match current {
+                Behaviour::Flee => None,
+                _ => Some(Behaviour::Flee),
+            },
+            Behaviour::Flee => Some(self.calm()),
+            _ if self.sees_player(senses) => match current {
+                Behaviour::Chase => None,
+                _ => Some(Behaviour::Chase),
+            },
+            Behaviour::Chase => Some(self.calm()),
+            Behaviour::Idle(timer) => {
+                if timer.finished() {
+                    Some(self.calm())
+                } else {
+                    None
+                }
+            }
+            Behaviour::Wander | Behaviour::Patrol(_) => {
+                let chance = self.rest_probability as f32 / 100.0 * delta;
+                if self.rest_time > 0.0 && thread_rng().gen::<f32>() < chance {
+                    Some(Behaviour::Idle(Timer::from_seconds(self.rest_time, false)))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+pub fn update_behaviour(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    player_distance: Res<PlayerDistance>,
+    danger_map: Res<DangerMap>,
+    mut query: Query<
+        (&Transform, &Personality, &mut Behaviour, Option<&Footprint>),
+        (With<Creature>, Without<Stop>),
+    >,
+) {
+    let delta = time.delta_seconds();
+    for (transform, personality, mut behaviour, footprint) in query.iter_mut() {
+        match &mut *behaviour {
+            Behaviour::Idle(timer) | Behaviour::Stunned(timer) => {
+                timer.tick(delta);
+            }
+            _ => {}
+        }
+        let size = footprint.map_or(1.0, |footprint| footprint.0);
+        let offset = Vec2::new(1.0, -1.0) * (size - 1.0) * TILE_WIDTH / 2.0;
+        let senses = match grid.to_tile(transform.translation.truncate() - offset) {
+            Some((x, y)) => Senses {
+                player_distance: player_distance.0.get(x, y),
+                in_danger: danger_map.get(x, y).is_some(),
+            },
+            None => continue,
+        };
+        if let Some(next) = personality.next(&behaviour, &senses, delta) {
+            *behaviour = next;
+        }
+    }
+}
@@ -2,21 +2,25 @@ use bevy::prelude::*;
 
 use crate::{
     assets::{
-        BombNumberBuffMaterial, BombTextureAtlas, FireTextureAtlas, PortalTextureAtlas,
-        PowerBuffMaterial, SpeedBuffMaterial,
+        BombNumberBuffMaterial, BombPassBuffMaterial, BombTextureAtlas, FireTextureAtlas,
+        LifeMaterial, PortalTextureAtlas, PowerBuffMaterial, SpeedBuffMaterial,
+        WallPassBuffMaterial,
     },
     components::{
-        Animation, Bomb, BombNumber, BombPower, Destructible, Direction, Ember, Fire, Player, Stop,
-        Wall, FIRE_ANIMATE_TIME,
+        Animation, Blast, Bomb, BombNumber, BombPower, Burning, Destructible, Direction, Ember,
+        Fire, Footprint, Intent, Invulnerable, Player, Stop, Wall, FIRE_ANIMATE_TIME,
     },
     entities::{
-        create_bomb, create_bomb_number_buff, create_center_fire, create_ember, create_portal,
-        create_power_buff, create_speed_buff,
+        create_bomb, create_bomb_number_buff, create_bomb_pass_buff, create_burning_buff,
+        create_center_fire, create_ember, create_portal, create_power_buff, create_shield_buff,
+        create_speed_buff, create_wall_pass_buff,
     },
-    events::GameEvents,
+    events::{DeathCause, GameEvents},
+    grid::{Grid, Tile},
+    portal::PORTAL_WAVE_COOLDOWN,
     resources::Map,
-    state::RunState,
-    utils::{vecs_xy_intersect, TILE_WIDTH},
+    ui::DrawBlinkTimer,
+    utils::{vecs_xy_intersect, vecs_xy_intersect_sized, TILE_WIDTH},
 };
 
 pub trait BombSystems {
@@ -24,7 +28,8 @@ pub trait BombSystems {
 }
 impl BombSystems for SystemStage {
     fn bomb_systems(&mut self) -> &mut Self {
-        self.add_system(space_to_set_bomb.system())
+        self.add_system(intent_to_set_bomb.system())
+            .add_system(intent_to_detonate.system())
             .add_system(bomb_trigger.system())
             .add_system(despawn_fire.system())
             .add_system(bomb_block_player.system())
@@ -32,6 +37,8 @@ impl BombSystems for SystemStage {
             .add_system(animate_bomb.system())
             .add_system(animate_fire.system())
             .add_system(ember_trigger.system())
+            .add_system(animate_burning.system())
+            .add_system(invulnerable_countdown.system())
     }
 }
 
@@ -54,47 +61,74 @@ impl BombBunble {
     }
 }
 
-fn space_to_set_bomb(
+fn intent_to_set_bomb(
     commands: &mut Commands,
     bomb_texture_atlas: Res<BombTextureAtlas>,
-    runstate: Res<RunState>,
-    keyboard_input: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
     bomb_position: Query<&Transform, With<Bomb>>,
     mut player_query: Query<
-        (&Transform, &BombPower, &mut BombNumber),
+        (Entity, &Transform, &BombPower, &mut BombNumber, &mut Intent),
         (With<Player>, Without<Stop>),
     >,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        if let Some(entity) = runstate.player {
-            for (transform, &power, mut number) in player_query.iter_mut() {
-                let position = transform.translation;
-                fn handle(n: f32) -> f32 {
-                    let a = n.floor();
-                    let b = n.fract();
-                    if b >= 0.5 {
-                        (a + 1.0) * TILE_WIDTH
-                    } else {
-                        a * TILE_WIDTH
-                    }
-                }
-                let number_x = position.x / TILE_WIDTH;
-                let number_y = position.y / TILE_WIDTH;
-                let one = Vec2::new(handle(number_x), handle(number_y));
+    for (entity, transform, &power, mut number, mut intent) in player_query.iter_mut() {
+        if !intent.bomb {
+            continue;
+        }
+        intent.bomb = false;
+        let position = transform.translation;
+        fn handle(n: f32) -> f32 {
+            let a = n.floor();
+            let b = n.fract();
+            if b >= 0.5 {
+                (a + 1.0) * TILE_WIDTH
+            } else {
+                a * TILE_WIDTH
+            }
+        }
+        let number_x = position.x / TILE_WIDTH;
+        let number_y = position.y / TILE_WIDTH;
+        let one = Vec2::new(handle(number_x), handle(number_y));
 
-                let mut is_not_exist = true;
-                'bomb: for bomb_position in bomb_position.iter() {
-                    if bomb_position.translation.truncate() == one {
-                        is_not_exist = false;
-                        break 'bomb;
-                    }
-                }
-                if is_not_exist && number.is_enough() {
-                    create_bomb(commands, one, bomb_texture_atlas.0.clone(), entity, power);
-                    number.current += 1;
-                }
+        // a player hiding inside a box can't put a bomb in there
+        let in_box = grid
+            .to_tile(one)
+            .map_or(false, |(x, y)| grid.get(x, y) == Some(Tile::Box));
+        let mut is_not_exist = !in_box;
+        'bomb: for bomb_position in bomb_position.iter() {
+            if bomb_position.translation.truncate() == one {
+                is_not_exist = false;
+                break 'bomb;
             }
         }
+        if is_not_exist && number.is_enough() {
+            create_bomb(commands, one, bomb_texture_atlas.0.clone(), entity, power);
+            number.current += 1;
+        }
+    }
+}
+fn intent_to_detonate(
+    mut player_query: Query<(Entity, &mut Intent), (With<Player>, Without<Stop>)>,
+    mut bomb_query: Query<&mut Bomb>,
+) {
+    for (entity, mut intent) in player_query.iter_mut() {
+        if !intent.detonate {
+            continue;
+        }
+        intent.detonate = false;
+        let oldest = bomb_query
+            .iter_mut()
+            .filter(|bomb| bomb.player == entity)
+            .max_by(|a, b| {
+                a.timer
+                    .elapsed()
+                    .partial_cmp(&b.timer.elapsed())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        if let Some(mut bomb) = oldest {
+            // the fuse runs out on the next tick
+            bomb.timer = Timer::from_seconds(0.0, false);
+        }
     }
 }
 fn animate_bomb(
@@ -164,6 +198,10 @@ fn bomb_trigger(
                 translation.truncate(),
                 fire_texture_atlas.0.clone(),
                 power.0,
+                Blast {
+                    bomb: entity,
+                    owner: bomb.player,
+                },
             );
             commands.despawn(entity);
             recovery_bomb_number_events.send(GameEvents::RecoveryBombNumber(bomb.player));
@@ -176,9 +214,9 @@ fn ember_trigger(
     time: Res<Time>,
     map: Res<Map>,
     fire_texture_atlas: Res<FireTextureAtlas>,
-    mut fire_query: Query<(&Transform, &mut Ember), With<Fire>>,
+    mut fire_query: Query<(&Transform, &mut Ember, &Blast), With<Fire>>,
 ) {
-    for (transform, mut ember) in fire_query.iter_mut() {
+    for (transform, mut ember, blast) in fire_query.iter_mut() {
         let power = ember.1;
         let translation = transform.translation;
         if ember.0.tick(time.delta_seconds()).just_finished() {
@@ -208,6 +246,7 @@ fn ember_trigger(
                             fire_texture_atlas.0.clone(),
                             Direction::Up,
                             i == (power as f32),
+                            *blast,
                         );
                     }
                 }
@@ -235,6 +274,7 @@ fn ember_trigger(
                             fire_texture_atlas.0.clone(),
                             Direction::Down,
                             i == (power as f32),
+                            *blast,
                         );
                     }
                 }
@@ -263,6 +303,7 @@ fn ember_trigger(
                             fire_texture_atlas.0.clone(),
                             Direction::Left,
                             i == (power as f32),
+                            *blast,
                         );
                     }
                 }
@@ -290,6 +331,7 @@ fn ember_trigger(
                             fire_texture_atlas.0.clone(),
                             Direction::Right,
                             i == (power as f32),
+                            *blast,
                         );
                     }
                 }
@@ -311,38 +353,61 @@ fn bomb_block_player(
     player_query: Query<&Transform, With<Player>>,
 ) {
     for (entity, bomb_position) in bomb_query.iter() {
-        for player_position in player_query.iter() {
-            if !vecs_xy_intersect(
+        // only solid once every bomber has stepped off it
+        if player_query.iter().all(|player_position| {
+            !vecs_xy_intersect(
                 &player_position.translation.truncate(),
                 &bomb_position.translation.truncate(),
-            ) {
-                commands.insert_one(entity, Wall);
-            }
+            )
+        }) {
+            commands.insert_one(entity, Wall);
         }
     }
 }
 
 fn bomb_destruction(
     commands: &mut Commands,
-    destructable_wall_query: Query<(Entity, &Transform, &Destructible), With<Destructible>>,
-    fire_query: Query<&Transform, With<Fire>>,
+    destructable_wall_query: Query<
+        (Entity, &Transform, &Destructible, Option<&Footprint>),
+        Without<Invulnerable>,
+    >,
+    fire_query: Query<(&Transform, &Blast), With<Fire>>,
+    map: Res<Map>,
     power_buff_material: Res<PowerBuffMaterial>,
     speed_buff_material: Res<SpeedBuffMaterial>,
     portal_texture_atlas: Res<PortalTextureAtlas>,
+    fire_texture_atlas: Res<FireTextureAtlas>,
     bomb_number_buff_material: Res<BombNumberBuffMaterial>,
-    mut game_over_events: ResMut<Events<GameEvents>>,
+    life_material: Res<LifeMaterial>,
+    wall_pass_buff_material: Res<WallPassBuffMaterial>,
+    bomb_pass_buff_material: Res<BombPassBuffMaterial>,
+    mut game_events: ResMut<Events<GameEvents>>,
 ) {
-    for (entity, transform, destructable) in destructable_wall_query.iter() {
+    for (entity, transform, destructable, footprint) in destructable_wall_query.iter() {
         let position = transform.translation;
-        let mut need_destroy = false;
-        'fire: for fire in fire_query.iter() {
-            if vecs_xy_intersect(&fire.translation.truncate(), &position.truncate()) {
-                need_destroy = true;
+        let size = footprint.map_or(1.0, |footprint| footprint.0);
+        let mut hit_by = None;
+        'fire: for (fire, blast) in fire_query.iter() {
+            if vecs_xy_intersect_sized(&fire.translation.truncate(), &position.truncate(), size) {
+                hit_by = Some(*blast);
                 break 'fire;
             }
         }
 
-        if need_destroy {
+        if let Some(blast) = hit_by {
+            match destructable {
+                Destructible::NormalBox
+                | Destructible::PowerBuffBox
+                | Destructible::SpeedBuffBox
+                | Destructible::BombNumberBuffBox
+                | Destructible::ShieldBuffBox
+                | Destructible::WallPassBuffBox
+                | Destructible::BombPassBuffBox
+                | Destructible::Portal => {
+                    game_events.send(GameEvents::BoxDestroyed(blast));
+                }
+                _ => {}
+            }
             match destructable {
                 Destructible::NormalBox => {
                     commands.despawn(entity);
@@ -363,19 +428,81 @@ fn bomb_destruction(
                         bomb_number_buff_material.0.clone(),
                     );
                 }
+                Destructible::ShieldBuffBox => {
+                    commands.despawn(entity);
+                    create_shield_buff(commands, position, life_material.0.clone());
+                }
+                Destructible::WallPassBuffBox => {
+                    commands.despawn(entity);
+                    create_wall_pass_buff(commands, position, wall_pass_buff_material.0.clone());
+                }
+                Destructible::BombPassBuffBox => {
+                    commands.despawn(entity);
+                    create_bomb_pass_buff(commands, position, bomb_pass_buff_material.0.clone());
+                }
                 Destructible::Portal => {
                     commands.despawn(entity);
                     create_portal(commands, position, portal_texture_atlas.0.clone());
                 }
+                Destructible::ExitPortal => {
+                    // one wave per blast
+                    commands.insert_one(entity, Invulnerable::new(PORTAL_WAVE_COOLDOWN));
+                    game_events.send(GameEvents::PortalHit(entity));
+                }
+                // the blast that destroys a box also catches a player hiding inside it
                 Destructible::Player => {
-                    commands.despawn(entity);
-
-                    game_over_events.send(GameEvents::GameOver);
+                    game_events.send(GameEvents::PlayerHit(entity, DeathCause::Fire(blast)));
                 }
                 Destructible::Creature => {
-                    commands.despawn(entity);
+                    game_events.send(GameEvents::CreatureHit(entity, blast));
+                }
+                Destructible::Buff => {
+                    if map.rules.burnable_buffs {
+                        commands.despawn(entity);
+                        create_burning_buff(commands, position, fire_texture_atlas.0.clone());
+                    }
                 }
             }
         }
     }
 }
+fn animate_burning(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Animation, &mut TextureAtlasSprite), With<Burning>>,
+) {
+    for (entity, mut animation, mut sprite) in query.iter_mut() {
+        animation.0.tick(time.delta_seconds());
+        if animation.0.just_finished() {
+            if sprite.index == 8 {
+                sprite.index = 9;
+            } else if sprite.index == 9 {
+                sprite.index = 10;
+            } else {
+                commands.despawn(entity);
+            }
+        }
+    }
+}
+fn invulnerable_countdown(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut Invulnerable,
+        Option<&DrawBlinkTimer>,
+        Option<&mut Visible>,
+    )>,
+) {
+    for (entity, mut invulnerable, blink, visible) in query.iter_mut() {
+        if invulnerable.0.tick(time.delta_seconds()).just_finished() {
+            commands.remove_one::<Invulnerable>(entity);
+            if blink.is_some() {
+                commands.remove_one::<DrawBlinkTimer>(entity);
+            }
+            if let Some(mut visible) = visible {
+                visible.is_visible = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+use crate::{
+    components::{BombNumber, BombPower, Buff, Direction, Intent, Player, Stop},
+    grid::{DangerMap, DistanceField, Grid, Tile, DIRECTIONS},
+    resources::Difficulty,
+};
+
+pub trait BotSystems {
+    fn bot_systems(&mut self) -> &mut Self;
+}
+impl BotSystems for SystemStage {
+    fn bot_systems(&mut self) -> &mut Self {
+        self.add_system(bot_think.system())
+            .add_system(bot_steer.system())
+    }
+}
+
+// How close to a tile centre a bot has to be to count as arrived.
+const ARRIVE_TOLERANCE: f32 = 4.0;
+// Tiles about to explode within this many seconds are not walked into.
+const DANGER_MARGIN: f32 = 1.0;
+// A bomb is only placed when a safe tile is at most this many steps away.
+const ESCAPE_STEPS: u32 = 4;
+// Pickups further away than this are not worth the detour.
+const PICKUP_RANGE: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub enum Plan {
+    Wait,
+    // run for the nearest tile no blast reaches
+    Escape,
+    // walk towards the nearest of these tiles
+    Seek(Vec<(usize, usize)>),
+}
+
+// A computer bomber, it drives the same `Intent` as the keyboard.
+pub struct Bot {
+    pub think: Timer,
+    // chance out of 100 to misjudge a decision
+    pub mistakes: i32,
+    pub plan: Plan,
+    // the tile it is walking to
+    pub heading: Option<(usize, usize)>,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Self {
+        let (reaction_time, mistakes) = match difficulty {
+            Difficulty::Easy => (0.6, 25),
+            Difficulty::Normal => (0.35, 10),
+            Difficulty::Hard => (0.15, 2),
+        };
+        Self {
+            think: Timer::from_seconds(reaction_time, true),
+            mistakes,
+            plan: Plan::Wait,
+            heading: None,
+        }
+    }
+}
+
+fn next_to_box(grid: &Grid, x: usize, y: usize) -> bool {
+    DIRECTIONS.iter().any(|&direction| {
+        grid.neighbour(x, y, direction)
+            .map_or(false, |(nx, ny)| grid.get(nx, ny) == Some(Tile::Box))
+    })
+}
+
+// Whether a bomb dropped here still leaves a way out in time.
+fn can_escape(grid: &Grid, danger_map: &DangerMap, x: usize, y: usize, power: i32) -> bool {
+    let blast = grid.blast(x, y, power);
+    let mut visited = vec![(x, y)];
+    let mut queue = VecDeque::new();
+    queue.push_back((x, y, 0));
+    while let Some((cx, cy, steps)) = queue.pop_front() {
+        if !blast.contains(&(cx, cy)) && danger_map.get(cx, cy).is_none() {
+            return true;
+        }
+        if steps == ESCAPE_STEPS {
+            continue;
+        }
+        for &direction in DIRECTIONS.iter() {
+            if let Some((nx, ny)) = grid.neighbour(cx, cy, direction) {
+                if grid.is_walkable(nx, ny)
+                    && !danger_map.is_dangerous(nx, ny, DANGER_MARGIN)
+                    && !visited.contains(&(nx, ny))
+                {
+                    visited.push((nx, ny));
+                    queue.push_back((nx, ny, steps + 1));
+                }
+            }
+        }
+    }
+    false
+}
+
+fn bot_think(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    danger_map: Res<DangerMap>,
+    mut bot_query: Query<
+        (
+            Entity,
+            &Transform,
+            &BombPower,
+            &BombNumber,
+            &mut Bot,
+            &mut Intent,
+        ),
+        Without<Stop>,
+    >,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Stop>)>,
+    buff_query: Query<&Transform, With<Buff>>,
+) {
+    let pickups: Vec<(usize, usize)> = buff_query
+        .iter()
+        .filter_map(|transform| grid.to_tile(transform.translation.truncate()))
+        .collect();
+    let box_spots: Vec<(usize, usize)> = grid
+        .free_tiles()
+        .into_iter()
+        .filter(|&(x, y)| next_to_box(&grid, x, y))
+        .collect();
+    for (entity, transform, power, number, mut bot, mut intent) in bot_query.iter_mut() {
+        if !bot.think.tick(time.delta_seconds()).just_finished() {
+            continue;
+        }
+        let (x, y) = match grid.to_tile(transform.translation.truncate()) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut rng = thread_rng();
+        let careless = rng.gen_range(0..100) < bot.mistakes;
+        if danger_map.get(x, y).is_some() {
+            // a careless bot freezes for a beat instead of running
+            bot.plan = if careless { Plan::Wait } else { Plan::Escape };
+            continue;
+        }
+        let opponents: Vec<(usize, usize)> = player_query
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .filter_map(|(_, transform)| grid.to_tile(transform.translation.truncate()))
+            .collect();
+        let blast = grid.blast(x, y, power.0);
+        let worth_a_bomb =
+            next_to_box(&grid, x, y) || opponents.iter().any(|opponent| blast.contains(opponent));
+        if worth_a_bomb
+            && number.is_enough()
+            && grid.get(x, y) != Some(Tile::Bomb)
+            && (careless || can_escape(&grid, &danger_map, x, y, power.0))
+        {
+            intent.bomb = true;
+            bot.plan = Plan::Escape;
+            continue;
+        }
+        if careless {
+            // wanders off for no reason
+            let wander: Vec<(usize, usize)> = DIRECTIONS
+                .iter()
+                .filter_map(|&direction| grid.neighbour(x, y, direction))
+                .filter(|&(nx, ny)| grid.is_walkable(nx, ny))
+                .collect();
+            bot.plan = wander
+                .choose(&mut rng)
+                .map_or(Plan::Wait, |&tile| Plan::Seek(vec![tile]));
+            continue;
+        }
+        let reachable = |targets: &Vec<(usize, usize)>, range: u32| {
+            DistanceField::new(&grid, targets.iter().copied())
+                .get(x, y)
+                .map_or(false, |distance| distance <= range)
+        };
+        bot.plan = if reachable(&pickups, PICKUP_RANGE) {
+            Plan::Seek(pickups.clone())
+        } else if reachable(&box_spots, u32::MAX) {
+            Plan::Seek(box_spots.clone())
+        } else if !opponents.is_empty() {
+            Plan::Seek(opponents)
+        } else {
+            Plan::Wait
+        };
+    }
+}
+
+// Which way to press to get from `position` to `goal`, lining up with the lane first.
+fn steer(position: Vec2, goal: Vec2) -> Option<Direction> {
+    let delta = goal - position;
+    let horizontal = if delta.x < 0.0 {
+        Direction::Left
+    } else {
+        Direction::Right
+    };
+    let vertical = if delta.y < 0.0 {
+        Direction::Down
+    } else {
+        Direction::Up
+    };
+    if delta.length() <= ARRIVE_TOLERANCE {
+        None
+    } else if delta.x.abs() >= delta.y.abs() {
+        if delta.y.abs() > ARRIVE_TOLERANCE {
+            Some(vertical)
+        } else {
+            Some(horizontal)
+        }
+    } else if delta.x.abs() > ARRIVE_TOLERANCE {
+        Some(horizontal)
+    } else {
+        Some(vertical)
+    }
+}
+
+fn bot_steer(
+    grid: Res<Grid>,
+    danger_map: Res<DangerMap>,
+    mut query: Query<(&Transform, &mut Bot, &mut Intent), Without<Stop>>,
+) {
+    for (transform, mut bot, mut intent) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let arrived = bot.heading.map_or(true, |(hx, hy)| {
+            (grid.to_position(hx, hy) - position).length() <= ARRIVE_TOLERANCE
+        });
+        if arrived {
+            bot.heading = match grid.to_tile(position) {
+                Some((x, y)) => {
+                    let step = match &bot.plan {
+                        Plan::Wait => None,
+                        Plan::Escape => danger_map.escape(&grid, x, y),
+                        Plan::Seek(targets) => {
+                            let field = DistanceField::new(&grid, targets.iter().copied());
+                            field.step(&grid, x, y).filter(|&direction| {
+                                grid.neighbour(x, y, direction).map_or(false, |(nx, ny)| {
+                                    !danger_map.is_dangerous(nx, ny, DANGER_MARGIN)
+                                })
+                            })
+                        }
+                    };
+                    step.and_then(|direction| grid.neighbour(x, y, direction))
+                }
+                None => None,
+            };
+        }
+        intent.direction = bot
+            .heading
+            .and_then(|(hx, hy)| steer(position, grid.to_position(hx, hy)));
+    }
+}
@@ -1,7 +1,18 @@
 use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng};
 
 use crate::{
-    components::{BombNumber, BombPower, Buff, Player, Velocity},
+    assets::{BombNumberBuffMaterial, PowerBuffMaterial, SpeedBuffMaterial},
+    components::{
+        Bomb, BombNumber, BombPass, BombPassTime, BombPower, Buff, Collected, Destructible, Player,
+        Shield, Stop, Velocity, Wall, WallPass, WallPassTime,
+    },
+    constants::OBJECT_LAYER,
+    entities::{create_bomb_number_buff, create_power_buff, create_speed_buff},
+    events::GameEvents,
+    grid::Grid,
+    physics::rebuild_player_body,
+    resources::Map,
     utils::vecs_xy_intersect,
 };
 
@@ -11,30 +22,163 @@ pub trait BuffSystems {
 impl BuffSystems for SystemStage {
     fn buff_systems(&mut self) -> &mut Self {
         self.add_system(buffs.system())
+            .add_system(pass_timeout.system())
+            .add_system(scatter_items.system())
     }
 }
 
+const PASS_TIME: f32 = 20.0;
+
 fn buffs(
     commands: &mut Commands,
+    mut game_events: ResMut<Events<GameEvents>>,
     buff_query: Query<(Entity, &Transform, &Buff), With<Buff>>,
-    mut player: Query<(&Transform, &mut BombPower, &mut BombNumber, &mut Velocity), With<Player>>,
+    mut player: Query<
+        (
+            Entity,
+            &Transform,
+            &mut BombPower,
+            &mut BombNumber,
+            &mut Velocity,
+            &mut Collected,
+        ),
+        With<Player>,
+    >,
 ) {
-    for (player, mut power, mut number, mut velocity) in player.iter_mut() {
+    for (player_entity, player, mut power, mut number, mut velocity, mut collected) in
+        player.iter_mut()
+    {
         let position = player.translation;
         for (entity, transform, buff) in buff_query.iter() {
             if vecs_xy_intersect(&transform.translation.truncate(), &position.truncate()) {
                 commands.despawn(entity);
+                game_events.send(GameEvents::ItemCollected(player_entity));
                 match buff {
                     Buff::PowerBuff => {
                         power.0 += 1;
+                        collected.power += 1;
                     }
                     Buff::SpeedBuff => {
                         // TODO:
                         velocity.0 = (velocity.0 * 1.2).min(400.0);
+                        collected.speed += 1;
                     }
                     Buff::BombNumberBuff => {
                         number.max += 1;
+                        collected.bomb_number += 1;
+                    }
+                    Buff::ShieldBuff => {
+                        commands.insert_one(player_entity, Shield);
+                    }
+                    Buff::WallPassBuff => {
+                        commands.insert(
+                            player_entity,
+                            (
+                                WallPass,
+                                WallPassTime(Timer::from_seconds(PASS_TIME, false)),
+                            ),
+                        );
+                        rebuild_player_body(commands, player_entity);
+                    }
+                    Buff::BombPassBuff => {
+                        commands.insert(
+                            player_entity,
+                            (
+                                BombPass,
+                                BombPassTime(Timer::from_seconds(PASS_TIME, false)),
+                            ),
+                        );
+                        rebuild_player_body(commands, player_entity);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A pass runs out once its time is up, but not while the player is still inside a box
+// or on a bomb it lets them through, the new body would be stuck in there.
+fn pass_timeout(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut wall_pass_query: Query<(Entity, &Transform, &mut WallPassTime), Without<Stop>>,
+    mut bomb_pass_query: Query<(Entity, &Transform, &mut BombPassTime), Without<Stop>>,
+    box_query: Query<&Transform, (With<Wall>, With<Destructible>, Without<Bomb>)>,
+    bomb_query: Query<&Transform, With<Bomb>>,
+) {
+    let mut rebuild = Vec::new();
+    for (entity, transform, mut pass_time) in wall_pass_query.iter_mut() {
+        if pass_time.0.tick(time.delta_seconds()).finished()
+            && !overlaps(transform, box_query.iter())
+        {
+            commands.remove::<(WallPass, WallPassTime)>(entity);
+            rebuild.push(entity);
+        }
+    }
+    for (entity, transform, mut pass_time) in bomb_pass_query.iter_mut() {
+        if pass_time.0.tick(time.delta_seconds()).finished()
+            && !overlaps(transform, bomb_query.iter())
+        {
+            commands.remove::<(BombPass, BombPassTime)>(entity);
+            if !rebuild.contains(&entity) {
+                rebuild.push(entity);
+            }
+        }
+    }
+    for entity in rebuild {
+        rebuild_player_body(commands, entity);
+    }
+}
+fn overlaps<'a>(position: &Transform, mut others: impl Iterator<Item = &'a Transform>) -> bool {
+    others.any(|transform| {
+        vecs_xy_intersect(
+            &transform.translation.truncate(),
+            &position.translation.truncate(),
+        )
+    })
+}
+
+fn scatter_items(
+    commands: &mut Commands,
+    game_events: Res<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    map: Res<Map>,
+    grid: Res<Grid>,
+    power_buff_material: Res<PowerBuffMaterial>,
+    speed_buff_material: Res<SpeedBuffMaterial>,
+    bomb_number_buff_material: Res<BombNumberBuffMaterial>,
+) {
+    for event in events_reader.iter(&game_events) {
+        if let GameEvents::ScatterItems(collected) = event {
+            if !map.rules.scatter_items {
+                continue;
+            }
+            let mut tiles = grid.free_tiles();
+            tiles.shuffle(&mut thread_rng());
+            let mut tiles = tiles.into_iter();
+            let buffs = (0..collected.power)
+                .map(|_| Buff::PowerBuff)
+                .chain((0..collected.speed).map(|_| Buff::SpeedBuff))
+                .chain((0..collected.bomb_number).map(|_| Buff::BombNumberBuff));
+            for buff in buffs {
+                let (x, y) = match tiles.next() {
+                    Some(tile) => tile,
+                    None => break,
+                };
+                let translation = grid.to_position(x, y).extend(OBJECT_LAYER);
+                match buff {
+                    Buff::PowerBuff => {
+                        create_power_buff(commands, translation, power_buff_material.0.clone())
+                    }
+                    Buff::SpeedBuff => {
+                        create_speed_buff(commands, translation, speed_buff_material.0.clone())
                     }
+                    Buff::BombNumberBuff => create_bomb_number_buff(
+                        commands,
+                        translation,
+                        bomb_number_buff_material.0.clone(),
+                    ),
+                    Buff::ShieldBuff | Buff::WallPassBuff | Buff::BombPassBuff => {}
                 }
             }
         }
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{GameMode, MAX_PLAYERS},
+    controls::{Action, Actions},
+    resources::Map,
+    state::{AppState, GameState},
+};
+
+// The character each slot plays, kept for the whole session.
+// It is always a shuffle of every character, so no two slots look alike.
+pub struct Characters(pub Vec<usize>);
+
+impl Default for Characters {
+    fn default() -> Self {
+        Self((0..MAX_PLAYERS).collect())
+    }
+}
+
+impl Characters {
+    pub fn get(&self, slot: usize) -> usize {
+        self.0.get(slot).copied().unwrap_or(slot)
+    }
+    // Steps to the next character no other player has picked,
+    // a seat nobody plays hands its character over.
+    fn cycle(&mut self, slot: usize, players: usize, forward: bool) {
+        let step = if forward { 1 } else { MAX_PLAYERS - 1 };
+        let current = self.0[slot];
+        let next = (1..MAX_PLAYERS)
+            .map(|i| (current + i * step) % MAX_PLAYERS)
+            .find(|&character| {
+                (0..players).all(|other| other == slot || self.0[other] != character)
+            });
+        if let Some(next) = next {
+            if let Some(holder) = self.0.iter().position(|&character| character == next) {
+                self.0.swap(slot, holder);
+            }
+        }
+    }
+}
+
+// Who has locked in a character, cleared every time the screen opens.
+#[derive(Default)]
+pub struct CharacterSelect {
+    pub ready: Vec<bool>,
+    // the press that opened the screen must not lock anyone in
+    opened: bool,
+}
+
+pub fn enter_character_select(game_mode: Res<GameMode>, mut select: ResMut<CharacterSelect>) {
+    select.ready = vec![false; game_mode.player_count()];
+    select.opened = true;
+}
+
+// Every player picks with left and right and locks in with their bomb button.
+pub fn character_select(
+    actions: Res<Actions>,
+    mut characters: ResMut<Characters>,
+    mut select: ResMut<CharacterSelect>,
+    mut map: ResMut<Map>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if select.opened {
+        select.opened = false;
+        return;
+    }
+    if actions.any_just_pressed(Action::Back) {
+        app_state.set_next(AppState::StartMenu).unwrap();
+        // leaves versus again
+        map.init();
+        return;
+    }
+    let players = select.ready.len();
+    for slot in 0..players {
+        let lock_in = actions.just_pressed(slot, Action::PlaceBomb)
+            || actions.just_pressed(slot, Action::Confirm);
+        if select.ready[slot] {
+            // changing your mind takes the lock off again
+            if actions.just_pressed(slot, Action::Detonate) {
+                select.ready[slot] = false;
+            }
+        } else if lock_in {
+            select.ready[slot] = true;
+        } else if actions.just_pressed(slot, Action::MoveLeft) {
+            characters.cycle(slot, players, false);
+        } else if actions.just_pressed(slot, Action::MoveRight) {
+            characters.cycle(slot, players, true);
+        }
+    }
+    if select.ready.iter().all(|&ready| ready) {
+        app_state.set_next(AppState::Game).unwrap();
+        game_state.set_next(GameState::Game).unwrap();
+    }
+}
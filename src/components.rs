@@ -11,10 +11,35 @@ pub enum Buff {
     PowerBuff,
     SpeedBuff,
     BombNumberBuff,
+    ShieldBuff,
+    WallPassBuff,
+    BombPassBuff,
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GameMode {
     SinglePlayer,
-    MultiPlayer,
+    // 2 to 4 players on one keyboard
+    MultiPlayer(usize),
+}
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::SinglePlayer
+    }
+}
+pub const MAX_PLAYERS: usize = 4;
+impl GameMode {
+    pub fn player_count(&self) -> usize {
+        match self {
+            GameMode::SinglePlayer => 1,
+            GameMode::MultiPlayer(count) => *count,
+        }
+    }
+    pub fn with_players(count: usize) -> Self {
+        match count.min(MAX_PLAYERS) {
+            0 | 1 => GameMode::SinglePlayer,
+            count => GameMode::MultiPlayer(count),
+        }
+    }
 }
 #[derive(Debug)]
 pub enum Destructible {
@@ -22,15 +47,54 @@ pub enum Destructible {
     PowerBuffBox,
     SpeedBuffBox,
     BombNumberBuffBox,
+    ShieldBuffBox,
+    WallPassBuffBox,
+    BombPassBuffBox,
     Portal,
+    ExitPortal,
     Player,
     Creature,
+    Buff,
 }
 pub struct MaxAndCurrent(i32, i32);
 pub struct Player {
     pub is_moving: bool,
 }
+// Played from the keyboard, the others are bots. Holds the player's slot, from 0.
+pub struct Human(pub usize);
+// What a bomber wants to do, written by the keyboard or by a bot.
+#[derive(Default)]
+pub struct Intent {
+    pub direction: Option<Direction>,
+    pub bomb: bool,
+    // sets off the oldest bomb of one's own early
+    pub detonate: bool,
+    // a turn pressed a moment ago and the seconds it is kept for, taken once the way opens
+    pub buffered: Option<(Direction, f32)>,
+}
+// Lives left, losing the last one is the end of the player.
+pub struct Lives(pub u32);
+// Where a player comes back after losing a life.
+pub struct SpawnPoint(pub Vec2);
+// The spawn point a versus bomber took, humans and bots alike, round wins are kept by it.
+pub struct Seat(pub usize);
+// Upgrades a player picked up during the round.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Collected {
+    pub power: i32,
+    pub bomb_number: i32,
+    pub speed: i32,
+}
 pub struct Portal;
+// Absorbs the next hit from fire or a creature.
+pub struct Shield;
+// Walks through boxes, but not through the indestructible walls.
+// Ghost creatures share it for good, players only hold it until their `WallPassTime` runs out.
+pub struct WallPass;
+pub struct BombPass;
+// How long a picked up pass has left.
+pub struct WallPassTime(pub Timer);
+pub struct BombPassTime(pub Timer);
 pub struct Stop;
 pub struct Velocity(pub f32);
 pub struct PlayerSensor;
@@ -77,7 +141,34 @@ impl Fire {
         Fire(Timer::from_seconds(EMBER_LIFETIME, false))
     }
 }
+// Ignores fire until the timer runs out.
+pub struct Invulnerable(pub Timer);
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Self {
+        Invulnerable(Timer::from_seconds(seconds, false))
+    }
+}
+pub struct Burning;
+pub struct HitPoints {
+    pub current: i32,
+    pub max: i32,
+}
+impl HitPoints {
+    pub fn new(max: i32) -> Self {
+        Self { current: max, max }
+    }
+}
+// Tints the sprite red for a moment after a hit.
+pub struct HurtFlash(pub Timer);
+// Side length in tiles of something bigger than a single tile.
+pub struct Footprint(pub f32);
 pub struct Ember(pub Timer, pub i32);
+// Which bomb a fire came from and who placed that bomb.
+#[derive(Clone, Copy, Debug)]
+pub struct Blast {
+    pub bomb: Entity,
+    pub owner: Entity,
+}
 impl Ember {
     pub fn new(power: i32) -> Self {
         Ember(Timer::from_seconds(EMBER_START_TIME, false), power)
@@ -107,6 +198,14 @@ impl Distribution<Direction> for Standard {
     }
 }
 impl Direction {
+    pub fn reverse(&self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Up => Direction::Down,
+            Direction::Right => Direction::Left,
+            Direction::Down => Direction::Up,
+        }
+    }
     pub fn into_dir(&self) -> Vector2<f32> {
         match self {
             Direction::Up => Vector2::new(-1.0, 0.0),
@@ -127,6 +226,15 @@ pub struct AnimateIndexs<T> {
     _mark: PhantomData<T>,
 }
 impl<T> AnimateIndexs<T> {
+    // Character 0 keeps the look of the single player game.
+    pub fn for_character(character: usize) -> Self {
+        match character % MAX_PLAYERS {
+            0 => Self::player4(),
+            1 => Self::player1(),
+            2 => Self::player2(),
+            _ => Self::player3(),
+        }
+    }
     pub fn player1() -> Self {
         let left = vec![10, 11, 12, 13];
         let right = vec![4, 5, 6, 7];
@@ -0,0 +1,366 @@
+use anyhow::{anyhow, Result};
+use bevy::prelude::*;
+
+use crate::{
+    components::MAX_PLAYERS,
+    gamepad::{pad_actions, PadSlots},
+    state::AppState,
+    touch::TouchControls,
+};
+
+// Bindings are kept next to the executable's working directory.
+#[cfg(not(target_arch = "wasm32"))]
+const CONFIG_PATH: &str = "controls.cfg";
+// Corner assist tolerance in tiles, more than half a tile makes no sense.
+const DEFAULT_CORNER_ASSIST: f32 = 0.4;
+const MAX_CORNER_ASSIST: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    PlaceBomb,
+    Detonate,
+    Pause,
+    Confirm,
+    Back,
+}
+
+pub const ACTIONS: [Action; 9] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::PlaceBomb,
+    Action::Detonate,
+    Action::Pause,
+    Action::Confirm,
+    Action::Back,
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "MoveUp",
+            Action::MoveDown => "MoveDown",
+            Action::MoveLeft => "MoveLeft",
+            Action::MoveRight => "MoveRight",
+            Action::PlaceBomb => "PlaceBomb",
+            Action::Detonate => "Detonate",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        ACTIONS.iter().copied().find(|action| action.name() == name)
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        [$((stringify!($key), KeyCode::$key)),*]
+    };
+}
+
+// The keys that can be bound, by the name they are saved under.
+#[rustfmt::skip]
+const KEYS: [(&str, KeyCode); 72] = key_names![
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadEnter, Up, Down, Left, Right, Space, Return, Escape, Back, Tab,
+    LShift, RShift, LControl, RControl, LAlt, RAlt,
+    Comma, Period, Slash, Semicolon, Apostrophe, LBracket, RBracket, Minus, Equals, Grave,
+];
+
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(_, known)| *known == key)
+        .map(|(name, _)| *name)
+}
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEYS.iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, key)| *key)
+}
+
+// One player's keys, an action may be left unbound.
+#[derive(Clone, Debug, Default)]
+pub struct ActionMap(Vec<(Action, KeyCode)>);
+
+impl ActionMap {
+    pub fn for_slot(slot: usize) -> Self {
+        let keys = match slot {
+            0 => vec![
+                (Action::MoveUp, KeyCode::Up),
+                (Action::MoveDown, KeyCode::Down),
+                (Action::MoveLeft, KeyCode::Left),
+                (Action::MoveRight, KeyCode::Right),
+                (Action::PlaceBomb, KeyCode::Space),
+                (Action::Detonate, KeyCode::RControl),
+                (Action::Pause, KeyCode::Escape),
+                (Action::Confirm, KeyCode::Return),
+                (Action::Back, KeyCode::Back),
+            ],
+            1 => vec![
+                (Action::MoveUp, KeyCode::W),
+                (Action::MoveDown, KeyCode::S),
+                (Action::MoveLeft, KeyCode::A),
+                (Action::MoveRight, KeyCode::D),
+                (Action::PlaceBomb, KeyCode::E),
+                (Action::Detonate, KeyCode::Q),
+            ],
+            2 => vec![
+                (Action::MoveUp, KeyCode::I),
+                (Action::MoveDown, KeyCode::K),
+                (Action::MoveLeft, KeyCode::J),
+                (Action::MoveRight, KeyCode::L),
+                (Action::PlaceBomb, KeyCode::O),
+                (Action::Detonate, KeyCode::U),
+            ],
+            _ => vec![
+                (Action::MoveUp, KeyCode::Numpad8),
+                (Action::MoveDown, KeyCode::Numpad5),
+                (Action::MoveLeft, KeyCode::Numpad4),
+                (Action::MoveRight, KeyCode::Numpad6),
+                (Action::PlaceBomb, KeyCode::Numpad0),
+                (Action::Detonate, KeyCode::NumpadEnter),
+            ],
+        };
+        Self(keys)
+    }
+    pub fn get(&self, action: Action) -> Option<KeyCode> {
+        self.0
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, key)| *key)
+    }
+    fn set(&mut self, action: Action, key: Option<KeyCode>) {
+        self.0.retain(|(bound, _)| *bound != action);
+        if let Some(key) = key {
+            self.0.push((action, key));
+        }
+    }
+    fn action(&self, key: KeyCode) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, bound)| *bound == key)
+            .map(|(action, _)| *action)
+    }
+}
+
+// Every player's action map, loaded from and saved to the config file.
+pub struct Bindings {
+    pub players: Vec<ActionMap>,
+    // how far off a lane, in tiles, turning still slides a player into it
+    pub corner_assist: f32,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            players: (0..MAX_PLAYERS).map(ActionMap::for_slot).collect(),
+            corner_assist: DEFAULT_CORNER_ASSIST,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn pressed(&self, input: &Input<KeyCode>, slot: usize, action: Action) -> bool {
+        self.key(slot, action)
+            .map_or(false, |key| input.pressed(key))
+    }
+    pub fn key(&self, slot: usize, action: Action) -> Option<KeyCode> {
+        self.players
+            .get(slot)
+            .and_then(|action_map| action_map.get(action))
+    }
+    // A key only ever does one thing, whoever had it before gets the replaced key instead.
+    pub fn bind(&mut self, slot: usize, action: Action, key: KeyCode) {
+        let replaced = self.key(slot, action);
+        for action_map in self.players.iter_mut() {
+            if let Some(taken) = action_map.action(key) {
+                action_map.set(taken, replaced);
+            }
+        }
+        if let Some(action_map) = self.players.get_mut(slot) {
+            action_map.set(action, Some(key));
+        }
+    }
+    // One `<player> <action> <key>` line per binding, players counted from 1,
+    // and an optional `corner_assist <tiles>` line.
+    fn parse(text: &str) -> Result<Self> {
+        let mut bindings = Self {
+            players: vec![ActionMap::default(); MAX_PLAYERS],
+            corner_assist: DEFAULT_CORNER_ASSIST,
+        };
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (player, action, key) = match fields.as_slice() {
+                ["corner_assist", tiles] => {
+                    bindings.corner_assist = tiles
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|tiles| (0.0..=MAX_CORNER_ASSIST).contains(tiles))
+                        .ok_or_else(|| anyhow!("bad corner assist: {}", tiles))?;
+                    continue;
+                }
+                [player, action, key] => (player, action, key),
+                _ => return Err(anyhow!("bad binding: {}", line)),
+            };
+            let slot = player
+                .parse::<usize>()
+                .ok()
+                .filter(|player| (1..=MAX_PLAYERS).contains(player))
+                .ok_or_else(|| anyhow!("bad player: {}", player))?
+                - 1;
+            let action =
+                Action::from_name(action).ok_or_else(|| anyhow!("bad action: {}", action))?;
+            let key = key_from_name(key).ok_or_else(|| anyhow!("bad key: {}", key))?;
+            bindings.bind(slot, action, key);
+        }
+        Ok(bindings)
+    }
+    fn to_text(&self) -> String {
+        let mut text = String::from("# <player> <action> <key>\n");
+        text.push_str(&format!("corner_assist {}\n", self.corner_assist));
+        for (slot, action_map) in self.players.iter().enumerate() {
+            for &action in ACTIONS.iter() {
+                if let Some(name) = action_map.get(action).and_then(key_name) {
+                    text.push_str(&format!("{} {} {}\n", slot + 1, action.name(), name));
+                }
+            }
+        }
+        text
+    }
+    // Falls back to the default keys when there is no usable file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        match std::fs::read_to_string(CONFIG_PATH) {
+            Ok(text) => Self::parse(&text).unwrap_or_else(|e| {
+                warn!("{}: {}, using the default controls", CONFIG_PATH, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        Self::default()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(CONFIG_PATH, self.to_text())?;
+        Ok(())
+    }
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// What every player holds down this frame, from the keyboard, their pad and the touch screen alike.
+#[derive(Default)]
+pub struct Actions {
+    held: Vec<(usize, Action)>,
+    previous: Vec<(usize, Action)>,
+}
+
+impl Actions {
+    pub fn pressed(&self, slot: usize, action: Action) -> bool {
+        self.held.contains(&(slot, action))
+    }
+    pub fn just_pressed(&self, slot: usize, action: Action) -> bool {
+        self.pressed(slot, action) && !self.previous.contains(&(slot, action))
+    }
+    // Menus answer to whichever player presses it.
+    pub fn any_just_pressed(&self, action: Action) -> bool {
+        (0..MAX_PLAYERS).any(|slot| self.just_pressed(slot, action))
+    }
+}
+
+pub fn update_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<Bindings>,
+    pad_slots: Res<PadSlots>,
+    touch_controls: Res<TouchControls>,
+    mut actions: ResMut<Actions>,
+) {
+    let mut held = Vec::new();
+    for slot in 0..MAX_PLAYERS {
+        for &action in ACTIONS.iter() {
+            if bindings.pressed(&keyboard_input, slot, action) {
+                held.push((slot, action));
+            }
+        }
+        let mut other_actions = match pad_slots.get(slot) {
+            Some(pad) => pad_actions(pad, &gamepad_buttons, &gamepad_axes),
+            None => Vec::new(),
+        };
+        if slot == 0 {
+            other_actions.extend(touch_controls.held.iter().copied());
+        }
+        for action in other_actions {
+            if !held.contains(&(slot, action)) {
+                held.push((slot, action));
+            }
+        }
+    }
+    actions.previous = std::mem::replace(&mut actions.held, held);
+}
+
+// Where the controls menu is at.
+#[derive(Default)]
+pub struct ControlsMenu {
+    pub slot: usize,
+    pub row: usize,
+    // the next key pressed is bound to the selected action
+    pub listening: bool,
+}
+
+// Up and down pick an action, left and right a player, confirm rebinds it.
+// Only keys are rebound here, pads keep their fixed layout.
+pub fn controls_menu(
+    input: Res<Input<KeyCode>>,
+    actions: Res<Actions>,
+    mut bindings: ResMut<Bindings>,
+    mut menu: ResMut<ControlsMenu>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if menu.listening {
+        let key = input
+            .get_just_pressed()
+            .copied()
+            .find(|&key| key_name(key).is_some());
+        if let Some(key) = key {
+            let action = ACTIONS[menu.row];
+            bindings.bind(menu.slot, action, key);
+            menu.listening = false;
+            if let Err(e) = bindings.save() {
+                error!("Saving the controls failed: {}", e);
+            }
+        }
+        return;
+    }
+    if actions.any_just_pressed(Action::Back) {
+        app_state.set_next(AppState::StartMenu).unwrap();
+    } else if actions.any_just_pressed(Action::Confirm) {
+        menu.listening = true;
+    } else if actions.just_pressed(0, Action::MoveUp) {
+        menu.row = (menu.row + ACTIONS.len() - 1) % ACTIONS.len();
+    } else if actions.just_pressed(0, Action::MoveDown) {
+        menu.row = (menu.row + 1) % ACTIONS.len();
+    } else if actions.just_pressed(0, Action::MoveLeft) {
+        menu.slot = (menu.slot + MAX_PLAYERS - 1) % MAX_PLAYERS;
+    } else if actions.just_pressed(0, Action::MoveRight) {
+        menu.slot = (menu.slot + 1) % MAX_PLAYERS;
+    }
+}
@@ -1,116 +1,547 @@
 use crate::{
-    components::{AnimateIndexs, Animation, Destructible, Direction, Stop, Velocity},
+    assets::{BombTextureAtlas, CreatureTextureAtlas},
+    behaviour::{update_behaviour, Behaviour, Personality},
+    components::{
+        AnimateIndexs, Animation, BombPower, Destructible, Direction, Footprint, HitPoints,
+        HurtFlash, Invulnerable, Stop, Velocity, WallPass,
+    },
+    entities::{create_bomb, spawn_creature},
     errors::querr_error_handler,
-    ui::DrawBlinkTimer,
+    events::GameEvents,
+    grid::{DangerMap, Grid, PlayerDistance, Tile, DIRECTIONS},
+    resources::{Difficulty, Map},
+    ui::{spawn_boss_health_bar, update_boss_health_bar},
+    utils::TILE_WIDTH,
 };
 use bevy::ecs::{Query, ResMut, SystemStage, With};
 use bevy::{ecs::QueryError, prelude::*};
 use bevy_rapier2d::{
-    na::Vector2, physics::RigidBodyHandleComponent, rapier::dynamics::RigidBodySet,
+    na::{Isometry2, Vector2},
+    physics::RigidBodyHandleComponent,
+    rapier::dynamics::RigidBodySet,
 };
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
 #[derive(Bundle)]
 pub struct CreatureBundle {
     creature: Creature,
+    kind: CreatureKind,
     direction: Direction,
     velocity: Velocity,
     destructible: Destructible,
     animation: Animation,
+    animate_indexs: AnimateIndexs<Creature>,
+    hit_points: HitPoints,
+    personality: Personality,
+    behaviour: Behaviour,
+    heading: Heading,
 }
 
-impl Default for CreatureBundle {
-    fn default() -> Self {
+impl CreatureBundle {
+    pub fn new(kind: CreatureKind) -> Self {
+        let stats = kind.stats();
         Self {
             creature: Creature,
+            kind,
             direction: Direction::Right,
-            velocity: Velocity(200.0),
+            velocity: Velocity(stats.speed),
             destructible: Destructible::Creature,
             animation: Animation(Timer::from_seconds(0.3, true)),
+            animate_indexs: kind.animate_indexs(),
+            hit_points: HitPoints::new(stats.hit_points),
+            personality: stats.personality,
+            behaviour: stats.personality.calm(),
+            heading: Heading::default(),
         }
     }
 }
 
 pub struct Creature;
 
-const TURN_PROBABILITY: i32 = 4;
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CreatureKind {
+    // the plain creature from creature.png
+    Walker,
+    // slow, drifts through boxes
+    Ghost,
+    // fast, tough and keeps out of blasts
+    Stalker,
+    // two tiles wide, calls minions and drops bombs
+    Boss,
+    // breaks into two smaller, faster copies of itself when it dies
+    Splitter { generation: u8 },
+}
+
+pub struct CreatureStats {
+    pub speed: f32,
+    pub hit_points: i32,
+    pub personality: Personality,
+    pub pass_boxes: bool,
+    pub score: u32,
+    // side length in tiles
+    pub size: f32,
+}
+
+impl CreatureKind {
+    pub fn from_tile(cell: i32) -> Option<Self> {
+        match cell {
+            7 => Some(CreatureKind::Walker),
+            13 => Some(CreatureKind::Ghost),
+            14 => Some(CreatureKind::Stalker),
+            15 => Some(CreatureKind::Boss),
+            17 => Some(CreatureKind::Splitter { generation: 0 }),
+            _ => None,
+        }
+    }
+    pub fn stats(&self) -> CreatureStats {
+        match self {
+            CreatureKind::Walker => CreatureStats {
+                speed: 200.0,
+                hit_points: 1,
+                personality: Personality {
+                    turn_probability: 50,
+                    chase_range: 6,
+                    flees: false,
+                    rest_probability: 10,
+                    rest_time: 1.0,
+                    stun_time: 0.5,
+                    patrol: &[],
+                },
+                pass_boxes: false,
+                score: 100,
+                size: 1.0,
+            },
+            CreatureKind::Ghost => CreatureStats {
+                speed: 120.0,
+                hit_points: 1,
+                personality: Personality {
+                    turn_probability: 80,
+                    chase_range: 0,
+                    flees: false,
+                    rest_probability: 20,
+                    rest_time: 1.5,
+                    stun_time: 0.3,
+                    patrol: &[],
+                },
+                pass_boxes: true,
+                score: 200,
+                size: 1.0,
+            },
+            CreatureKind::Stalker => CreatureStats {
+                speed: 250.0,
+                hit_points: 2,
+                personality: Personality {
+                    turn_probability: 30,
+                    chase_range: 10,
+                    flees: true,
+                    rest_probability: 0,
+                    rest_time: 0.0,
+                    stun_time: 0.3,
+                    patrol: &[],
+                },
+                pass_boxes: false,
+                score: 400,
+                size: 1.0,
+            },
+            CreatureKind::Boss => CreatureStats {
+                speed: 80.0,
+                hit_points: 12,
+                personality: Personality {
+                    turn_probability: 30,
+                    chase_range: 0,
+                    flees: false,
+                    rest_probability: 0,
+                    rest_time: 0.0,
+                    stun_time: 0.0,
+                    patrol: &BOSS_PATROL,
+                },
+                pass_boxes: false,
+                score: 5000,
+                size: 2.0,
+            },
+            CreatureKind::Splitter { generation } => {
+                let generation = *generation as f32;
+                CreatureStats {
+                    speed: 150.0 * (1.0 + 0.3 * generation),
+                    hit_points: 1,
+                    personality: Personality {
+                        turn_probability: 60,
+                        chase_range: 0,
+                        flees: false,
+                        rest_probability: 0,
+                        rest_time: 0.0,
+                        stun_time: 0.0,
+                        patrol: &[],
+                    },
+                    pass_boxes: false,
+                    score: 300,
+                    size: 1.0 - 0.25 * generation,
+                }
+            }
+        }
+    }
+    // The two creatures left behind, if this kind splits at all.
+    pub fn split(&self) -> Option<CreatureKind> {
+        match self {
+            CreatureKind::Splitter { generation } if *generation < MAX_SPLIT_GENERATION => {
+                Some(CreatureKind::Splitter {
+                    generation: generation + 1,
+                })
+            }
+            _ => None,
+        }
+    }
+    // Every kind walks with the frames from creature.png, so none of them looks like a bomber.
+    pub fn animate_indexs(&self) -> AnimateIndexs<Creature> {
+        AnimateIndexs::player1()
+    }
+    // What tells the kinds apart on the creature sheet.
+    pub fn tint(&self) -> Color {
+        match self {
+            CreatureKind::Walker => Color::WHITE,
+            CreatureKind::Ghost => Color::rgba(0.7, 0.85, 1.0, 0.6),
+            CreatureKind::Stalker => Color::rgb(0.75, 0.45, 1.0),
+            CreatureKind::Boss => Color::WHITE,
+            CreatureKind::Splitter { .. } => Color::rgb(0.5, 1.0, 0.5),
+        }
+    }
+}
+
+// The top left tile a creature is walking to; it only chooses the next one on arrival.
+#[derive(Default)]
+pub struct Heading(pub Option<(usize, usize)>);
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BossAttack {
+    SpawnMinions,
+    DropBombs,
+}
+pub struct Boss {
+    pub timer: Timer,
+    pub next: BossAttack,
+}
+impl Default for Boss {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(BOSS_ATTACK_TIME, true),
+            next: BossAttack::DropBombs,
+        }
+    }
+}
+
+// How close to a tile centre a creature has to be to count as arrived.
+const TURN_TOLERANCE: f32 = 4.0;
+// Tiles about to explode within this many seconds count as burning.
+const DANGER_MARGIN: f32 = 0.5;
+const CREATURE_INVULNERABLE_TIME: f32 = 0.8;
+// Splitters stop splitting after this many generations.
+const MAX_SPLIT_GENERATION: u8 = 2;
+// Long enough for the blast that split the parent to burn out.
+const SPLIT_GRACE_TIME: f32 = 1.0;
+const BOSS_ATTACK_TIME: f32 = 4.0;
+const BOSS_MAX_MINIONS: usize = 4;
+const BOSS_BOMBS: usize = 3;
+const BOSS_BOMB_POWER: i32 = 2;
+// a loop around the middle of the boss arena
+const BOSS_PATROL: [Direction; 12] = [
+    Direction::Left,
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Right,
+    Direction::Right,
+    Direction::Right,
+    Direction::Down,
+    Direction::Down,
+    Direction::Left,
+    Direction::Left,
+    Direction::Up,
+];
 pub trait CreatureSystems {
     fn creature_systems(&mut self) -> &mut Self;
 }
 impl CreatureSystems for SystemStage {
     fn creature_systems(&mut self) -> &mut Self {
-        self.add_system(
-            creature_movement
-                .system()
-                .chain(querr_error_handler.system()),
-        )
-        .add_system(despawn_player.system())
-        .add_system(animate_creature.system())
+        self.add_system(apply_difficulty.system())
+            .add_system(update_behaviour.system())
+            .add_system(
+                creature_movement
+                    .system()
+                    .chain(querr_error_handler.system()),
+            )
+            .add_system(animate_creature.system())
+            .add_system(creature_hit.system())
+            .add_system(hurt_flash.system())
+            .add_system(boss_attack.system())
+            .add_system(spawn_boss_health_bar.system())
+            .add_system(update_boss_health_bar.system())
+    }
+}
+
+// New creatures, wherever they come from, get tougher with the level and preset.
+fn apply_difficulty(
+    map: Res<Map>,
+    difficulty: Res<Difficulty>,
+    mut query: Query<(&mut Velocity, &mut Personality), Added<Creature>>,
+) {
+    let scaling = difficulty.scaling(map.level());
+    for (mut velocity, mut personality) in query.iter_mut() {
+        velocity.0 *= scaling.speed;
+        if personality.chase_range > 0 {
+            personality.chase_range += scaling.chase_range;
+        }
+        personality.flees |= scaling.flees;
     }
 }
 
 fn creature_movement(
-    mut query: Query<(Entity, &Velocity, &mut Direction), (With<Creature>, Without<Stop>)>,
+    time: Res<Time>,
+    grid: Res<Grid>,
+    player_distance: Res<PlayerDistance>,
+    danger_map: Res<DangerMap>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &Personality,
+            &mut Behaviour,
+            &mut Heading,
+            &mut Direction,
+            (Option<&WallPass>, Option<&Footprint>),
+        ),
+        (With<Creature>, Without<Stop>),
+    >,
     mut rigid_body_handle_query: Query<&mut RigidBodyHandleComponent>,
     mut rigid_body_set: ResMut<RigidBodySet>,
 ) -> Result<(), QueryError> {
-    for (entity, velocity, mut direction) in query.iter_mut() {
+    for (
+        entity,
+        transform,
+        velocity,
+        personality,
+        mut behaviour,
+        mut heading,
+        mut direction,
+        (wall_pass, footprint),
+    ) in query.iter_mut()
+    {
         let rigid_body_handle =
             rigid_body_handle_query.get_component_mut::<RigidBodyHandleComponent>(entity)?;
-        let mut rng = thread_rng();
-        let n = rng.gen_range(0..=100);
-        if n < TURN_PROBABILITY {
-            // only change ocassionally
-            *direction = rand::random();
-        }
-        let linvel = match *direction {
-            Direction::Left => Vector2::new(-velocity.0, 0.0),
-            Direction::Up => Vector2::new(0.0, velocity.0),
-            Direction::Right => Vector2::new(velocity.0, 0.0),
-            Direction::Down => Vector2::new(0.0, -velocity.0),
+        let rigid_body = match rigid_body_set.get_mut(rigid_body_handle.handle()) {
+            Some(rigid_body) => rigid_body,
+            None => {
+                error!("Get rigid body fail!");
+                continue;
+            }
         };
-        if let Some(rigid_body) = rigid_body_set.get_mut(rigid_body_handle.handle()) {
-            //info!("OK!");
-            rigid_body.set_linvel(linvel, true);
+        if let Behaviour::Stunned(_) = *behaviour {
+            rigid_body.set_linvel(Vector2::new(0.0, 0.0), true);
+            continue;
+        }
+        let size = footprint.map_or(1.0, |footprint| footprint.0);
+        // a big creature is steered by its top left tile
+        let offset = Vec2::new(1.0, -1.0) * (size - 1.0) * TILE_WIDTH / 2.0;
+        let steering = Steering {
+            grid: &grid,
+            player_distance: &player_distance,
+            danger_map: &danger_map,
+            size: size.round() as usize,
+            pass_boxes: wall_pass.is_some(),
+        };
+        let position = transform.translation.truncate();
+        let (x, y) = match heading.0.or_else(|| grid.to_tile(position - offset)) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        let mut target = (x, y);
+        let centre = grid.to_position(x, y) + offset;
+        let step = velocity.0 * time.delta_seconds();
+        if (centre - position).length() <= step.max(TURN_TOLERANCE) {
+            // on the tile centre, line up and pick the next tile
+            rigid_body.set_position(Isometry2::translation(centre.x, centre.y), true);
+            if let Some(next) = steering.choose((x, y), *direction, personality, &mut behaviour) {
+                *direction = next;
+                if let Some(tile) = grid.neighbour(x, y, next) {
+                    target = tile;
+                }
+            }
         } else {
-            error!("Get rigid body fail!");
+            // between tiles, only turn back when the way ahead closes
+            let reverse = direction.reverse();
+            let behind = grid
+                .neighbour(x, y, reverse)
+                .filter(|&(bx, by)| steering.fits(bx, by));
+            let into_danger = personality.flees
+                && danger_map.is_dangerous(x, y, DANGER_MARGIN)
+                && behind.map_or(false, |(bx, by)| {
+                    !danger_map.is_dangerous(bx, by, DANGER_MARGIN)
+                });
+            if !steering.fits(x, y) || into_danger {
+                if let Some(tile) = behind {
+                    *direction = reverse;
+                    target = tile;
+                }
+            }
         }
+        heading.0 = Some(target);
+        let goal = grid.to_position(target.0, target.1) + offset;
+        let linvel = if target == (x, y) && (goal - position).length() <= TURN_TOLERANCE {
+            Vector2::new(0.0, 0.0)
+        } else {
+            let towards = (goal - position).normalize() * velocity.0;
+            Vector2::new(towards.x, towards.y)
+        };
+        rigid_body.set_linvel(linvel, true);
     }
     Ok(())
 }
 
-#[derive(Bundle)]
-pub struct StopAndFlashing(Stop, DrawBlinkTimer, Timer);
-impl Default for StopAndFlashing {
-    fn default() -> Self {
-        Self(
-            Stop,
-            DrawBlinkTimer(Timer::from_seconds(0.2, true)), //TODO:Slow here
-            Timer::from_seconds(3.0, false),
-        )
+// What a creature of some size needs to know to pick its next tile.
+struct Steering<'a> {
+    grid: &'a Grid,
+    player_distance: &'a PlayerDistance,
+    danger_map: &'a DangerMap,
+    size: usize,
+    pass_boxes: bool,
+}
+
+impl<'a> Steering<'a> {
+    fn fits(&self, x: usize, y: usize) -> bool {
+        (0..self.size).all(|dy| {
+            (0..self.size).all(|dx| match self.grid.get(x + dx, y + dy) {
+                Some(Tile::Floor) | Some(Tile::Item) => true,
+                Some(Tile::Box) => self.pass_boxes,
+                _ => false,
+            })
+        })
+    }
+    fn is_open(&self, x: usize, y: usize, direction: Direction) -> bool {
+        self.grid
+            .neighbour(x, y, direction)
+            .map_or(false, |(nx, ny)| self.fits(nx, ny))
+    }
+    // Keeps going along a corridor and only decides at intersections and dead ends.
+    fn wander(
+        &self,
+        (x, y): (usize, usize),
+        direction: Direction,
+        turn_probability: i32,
+    ) -> Option<Direction> {
+        let reverse = direction.reverse();
+        let sides: Vec<Direction> = DIRECTIONS
+            .iter()
+            .copied()
+            .filter(|&turn| turn != direction && turn != reverse && self.is_open(x, y, turn))
+            .collect();
+        let forward = self.is_open(x, y, direction);
+        let mut rng = thread_rng();
+        if !sides.is_empty() && (!forward || rng.gen_range(0..100) < turn_probability) {
+            sides.choose(&mut rng).copied()
+        } else if forward {
+            Some(direction)
+        } else if self.is_open(x, y, reverse) {
+            Some(reverse)
+        } else {
+            None
+        }
+    }
+    // The next step on a tile centre, according to what the creature is doing.
+    fn choose(
+        &self,
+        (x, y): (usize, usize),
+        direction: Direction,
+        personality: &Personality,
+        behaviour: &mut Behaviour,
+    ) -> Option<Direction> {
+        let next = match behaviour {
+            Behaviour::Idle(_) | Behaviour::Stunned(_) => return None,
+            Behaviour::Flee => return self.danger_map.escape(self.grid, x, y),
+            Behaviour::Chase => self
+                .player_distance
+                .0
+                .step(self.grid, x, y)
+                .or_else(|| self.wander((x, y), direction, personality.turn_probability)),
+            Behaviour::Patrol(step) => {
+                let route = personality.patrol;
+                let planned = route[*step % route.len()];
+                *step = (*step + 1) % route.len();
+                if self.is_open(x, y, planned) {
+                    Some(planned)
+                } else {
+                    None
+                }
+            }
+            Behaviour::Wander => self.wander((x, y), direction, personality.turn_probability),
+        };
+        let next = if personality.flees {
+            next.map(|next| avoid(self.grid, self.danger_map, x, y, next, true))
+        } else {
+            next
+        };
+        next.filter(|&next| self.is_open(x, y, next))
     }
 }
-fn despawn_player(
-    commands: &mut Commands,
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut Timer), (With<Stop>, With<DrawBlinkTimer>)>,
-) {
-    for (entity, mut timer) in query.iter_mut() {
-        if timer.tick(time.delta_seconds()).just_finished() {
-            commands.despawn_recursive(entity);
+
+fn avoid(
+    grid: &Grid,
+    danger_map: &DangerMap,
+    x: usize,
+    y: usize,
+    direction: Direction,
+    at_centre: bool,
+) -> Direction {
+    let is_safe = |direction: Direction| match grid.neighbour(x, y, direction) {
+        Some((nx, ny)) => {
+            grid.is_walkable(nx, ny) && !danger_map.is_dangerous(nx, ny, DANGER_MARGIN)
         }
+        None => false,
+    };
+    if danger_map.get(x, y).is_some() {
+        // standing in a blast zone, leave by the shortest way out
+        if at_centre {
+            if let Some(escape) = danger_map.escape(grid, x, y) {
+                return escape;
+            }
+        }
+        return direction;
+    }
+    let ahead = grid.neighbour(x, y, direction);
+    if !ahead.map_or(false, |(nx, ny)| {
+        danger_map.is_dangerous(nx, ny, DANGER_MARGIN)
+    }) {
+        return direction;
+    }
+    let reverse = direction.reverse();
+    if at_centre {
+        if let Some(&turn) = DIRECTIONS
+            .iter()
+            .find(|&&turn| turn != direction && turn != reverse && is_safe(turn))
+        {
+            return turn;
+        }
+    }
+    if is_safe(reverse) {
+        reverse
+    } else {
+        direction
     }
 }
+
 // animate
 fn animate_creature(
     time: Res<Time>,
-    animate_date: Res<AnimateIndexs<Creature>>,
     mut query: Query<
-        (&mut Animation, &mut TextureAtlasSprite, &Direction),
+        (
+            &mut Animation,
+            &mut TextureAtlasSprite,
+            &Direction,
+            &AnimateIndexs<Creature>,
+        ),
         (With<Creature>, Without<Stop>),
     >,
 ) {
-    for (mut animation, mut sprite, direction) in query.iter_mut() {
+    for (mut animation, mut sprite, direction, animate_date) in query.iter_mut() {
         let indexs = match direction {
             Direction::Left => &animate_date.left,
             Direction::Up => &animate_date.up,
@@ -146,3 +577,146 @@ fn animate_creature(
         }
     }
 }
+
+fn creature_hit(
+    commands: &mut Commands,
+    grid: Res<Grid>,
+    creature_texture_atlas: Res<CreatureTextureAtlas>,
+    mut game_events: ResMut<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    mut query: Query<
+        (
+            &Transform,
+            &CreatureKind,
+            &mut HitPoints,
+            &Personality,
+            &mut Behaviour,
+        ),
+        (With<Creature>, Without<Invulnerable>),
+    >,
+) {
+    let mut hits = Vec::new();
+    for event in events_reader.iter(&game_events) {
+        if let GameEvents::CreatureHit(entity, blast) = event {
+            // a boss is not hurt by the bombs it drops itself
+            if blast.owner != *entity && !hits.iter().any(|(hit, _)| hit == entity) {
+                hits.push((*entity, *blast));
+            }
+        }
+    }
+    for (entity, blast) in hits {
+        if let Ok((transform, kind, mut hit_points, personality, mut behaviour)) =
+            query.get_mut(entity)
+        {
+            hit_points.current -= 1;
+            if hit_points.current > 0 {
+                if let Some(stunned) = personality.stunned() {
+                    *behaviour = stunned;
+                }
+                commands.insert(
+                    entity,
+                    (
+                        Invulnerable::new(CREATURE_INVULNERABLE_TIME),
+                        HurtFlash(Timer::from_seconds(CREATURE_INVULNERABLE_TIME, false)),
+                    ),
+                );
+            } else {
+                commands.despawn(entity);
+                game_events.send(GameEvents::CreatureKilled(*kind, blast));
+                if let Some(child) = kind.split() {
+                    let position = transform.translation.truncate();
+                    for tile in split_tiles(&grid, position) {
+                        spawn_creature(commands, tile, creature_texture_atlas.0.clone(), child)
+                            .with(Invulnerable::new(SPLIT_GRACE_TIME));
+                    }
+                }
+            }
+        }
+    }
+}
+// Free tiles next to a dead splitter for its two children, its own tile if there is no room.
+fn split_tiles(grid: &Grid, position: Vec2) -> Vec<Vec2> {
+    let (x, y) = match grid.to_tile(position) {
+        Some(tile) => tile,
+        None => return vec![position, position],
+    };
+    let mut tiles: Vec<Vec2> = DIRECTIONS
+        .iter()
+        .filter_map(|&direction| grid.neighbour(x, y, direction))
+        .filter(|&(nx, ny)| grid.get(nx, ny) == Some(Tile::Floor))
+        .map(|(nx, ny)| grid.to_position(nx, ny))
+        .collect();
+    tiles.shuffle(&mut thread_rng());
+    tiles.push(grid.to_position(x, y));
+    tiles.push(grid.to_position(x, y));
+    tiles.truncate(2);
+    tiles
+}
+fn hurt_flash(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &CreatureKind,
+        &mut HurtFlash,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (entity, kind, mut flash, mut sprite) in query.iter_mut() {
+        if flash.0.tick(time.delta_seconds()).finished() {
+            sprite.color = kind.tint();
+            commands.remove_one::<HurtFlash>(entity);
+        } else {
+            sprite.color = Color::rgb(1.0, 0.3, 0.3);
+        }
+    }
+}
+fn boss_attack(
+    commands: &mut Commands,
+    time: Res<Time>,
+    grid: Res<Grid>,
+    creature_texture_atlas: Res<CreatureTextureAtlas>,
+    bomb_texture_atlas: Res<BombTextureAtlas>,
+    mut boss_query: Query<(Entity, &Transform, &mut Boss), Without<Stop>>,
+    minion_query: Query<&Creature, Without<Boss>>,
+) {
+    for (entity, transform, mut boss) in boss_query.iter_mut() {
+        if !boss.timer.tick(time.delta_seconds()).just_finished() {
+            continue;
+        }
+        let centre = transform.translation.truncate();
+        let mut tiles: Vec<Vec2> = grid
+            .free_tiles()
+            .into_iter()
+            .map(|(x, y)| grid.to_position(x, y))
+            .filter(|position| (*position - centre).length() < TILE_WIDTH * 2.5)
+            .collect();
+        tiles.shuffle(&mut thread_rng());
+        match boss.next {
+            BossAttack::SpawnMinions => {
+                let room = BOSS_MAX_MINIONS.saturating_sub(minion_query.iter().count());
+                for &position in tiles.iter().take(room.min(2)) {
+                    spawn_creature(
+                        commands,
+                        position,
+                        creature_texture_atlas.0.clone(),
+                        CreatureKind::Walker,
+                    );
+                }
+                boss.next = BossAttack::DropBombs;
+            }
+            BossAttack::DropBombs => {
+                for &position in tiles.iter().take(BOSS_BOMBS) {
+                    create_bomb(
+                        commands,
+                        position,
+                        bomb_texture_atlas.0.clone(),
+                        entity,
+                        BombPower(BOSS_BOMB_POWER),
+                    );
+                }
+                boss.next = BossAttack::SpawnMinions;
+            }
+        }
+    }
+}
@@ -2,10 +2,17 @@ use bevy::prelude::*;
 
 use crate::{
     bomb::{BombBunble, FireBundle},
-    components::{BombPower, Buff, Destructible, Direction, Fire, InGame, Portal, Wall, Way},
+    bot::Bot,
+    components::{
+        AnimateIndexs, Animation, Blast, BombPower, Buff, Burning, Destructible, Direction, Fire,
+        Footprint, Human, InGame, Invulnerable, Lives, Portal, Seat, SpawnPoint, Wall, WallPass,
+        Way, FIRE_ANIMATE_TIME, FIRE_LIFETIME,
+    },
     constants::{FLOOR_LAYER, OBJECT_LAYER, PLAYER_LAYER},
-    creatures::CreatureBundle,
-    player::PlayerBundle,
+    creatures::{Boss, CreatureBundle, CreatureKind},
+    player::{HeldDirections, PlayerBundle},
+    portal::PortalWaves,
+    resources::Difficulty,
     utils::{SCALE, TILE_WIDTH},
 };
 
@@ -99,7 +106,10 @@ fn create_buff(
         scale: Vec3::splat(scale),
         ..Default::default()
     };
+    // A buff revealed by a blast must survive that same blast.
     create_sprite(commands, transform, material_handle)
+        .with(Destructible::Buff)
+        .with(Invulnerable::new(FIRE_LIFETIME))
 }
 pub fn create_normal_wall(
     commands: &mut Commands,
@@ -141,17 +151,55 @@ pub fn create_player(
     translation: Vec2,
     texture_handle: Handle<TextureAtlas>,
     player_texture_handle: Handle<TextureAtlas>,
-    player_animate_index: u32,
+    slot: usize,
+    character: usize,
+    lives: u32,
 ) -> Option<Entity> {
     create_green_way(commands, translation, texture_handle);
     let transform = create_transform(translation, PLAYER_LAYER);
+    let animate_indexs = AnimateIndexs::for_character(character);
+    create_sprite_sheet(
+        commands,
+        transform,
+        player_texture_handle,
+        animate_indexs.up[0],
+    )
+    .with_bundle(PlayerBundle::new(animate_indexs))
+    .with(Human(slot))
+    .with(Seat(slot))
+    .with(HeldDirections::default())
+    .with(Lives(lives))
+    .with(SpawnPoint(translation))
+    .current_entity()
+}
+pub fn create_bot(
+    commands: &mut Commands,
+    translation: Vec2,
+    texture_handle: Handle<TextureAtlas>,
+    player_texture_handle: Handle<TextureAtlas>,
+    character: usize,
+    difficulty: Difficulty,
+    lives: u32,
+) -> Option<Entity> {
+    create_green_way(commands, translation, texture_handle);
+    let transform = create_transform(translation, PLAYER_LAYER);
+    let animate_indexs = AnimateIndexs::for_character(character);
+    let player_animate_index = animate_indexs.up[0];
     create_sprite_sheet(
         commands,
         transform,
         player_texture_handle,
         player_animate_index,
     )
-    .with_bundle(PlayerBundle::default())
+    .with_bundle(PlayerBundle::new(animate_indexs))
+    .with(Bot::new(difficulty))
+    .with(Lives(lives))
+    .with(SpawnPoint(translation))
+    // tinted to tell it apart from the humans
+    .with(TextureAtlasSprite {
+        index: player_animate_index,
+        color: Color::rgb(1.0, 0.6, 0.6),
+    })
     .current_entity()
 }
 pub fn create_bomb_number_buff_box(
@@ -176,6 +224,30 @@ pub fn create_speed_buff_box(
 ) {
     create_destructible_obj(commands, translation, texture_handle).with(Destructible::SpeedBuffBox);
 }
+pub fn create_shield_buff_box(
+    commands: &mut Commands,
+    translation: Vec2,
+    texture_handle: Handle<TextureAtlas>,
+) {
+    create_destructible_obj(commands, translation, texture_handle)
+        .with(Destructible::ShieldBuffBox);
+}
+pub fn create_wall_pass_buff_box(
+    commands: &mut Commands,
+    translation: Vec2,
+    texture_handle: Handle<TextureAtlas>,
+) {
+    create_destructible_obj(commands, translation, texture_handle)
+        .with(Destructible::WallPassBuffBox);
+}
+pub fn create_bomb_pass_buff_box(
+    commands: &mut Commands,
+    translation: Vec2,
+    texture_handle: Handle<TextureAtlas>,
+) {
+    create_destructible_obj(commands, translation, texture_handle)
+        .with(Destructible::BombPassBuffBox);
+}
 pub fn create_portal_box(
     commands: &mut Commands,
     translation: Vec2,
@@ -188,11 +260,40 @@ pub fn create_creature(
     translation: Vec2,
     texture_handle: Handle<TextureAtlas>,
     creature_texture_handle: Handle<TextureAtlas>,
+    kind: CreatureKind,
 ) {
     create_green_way(commands, translation, texture_handle);
-    let transform = create_transform(translation, PLAYER_LAYER);
-    create_sprite_sheet(commands, transform, creature_texture_handle, 0)
-        .with_bundle(CreatureBundle::default());
+    // a big creature's tile is its top left corner
+    let offset = (kind.stats().size - 1.0) * TILE_WIDTH / 2.0;
+    let centre = translation + Vec2::new(offset, -offset);
+    spawn_creature(commands, centre, creature_texture_handle, kind);
+}
+pub fn spawn_creature(
+    commands: &mut Commands,
+    translation: Vec2,
+    creature_texture_handle: Handle<TextureAtlas>,
+    kind: CreatureKind,
+) -> &mut Commands {
+    let stats = kind.stats();
+    let index = kind.animate_indexs().down[0];
+    let mut transform = create_transform(translation, PLAYER_LAYER);
+    transform.scale = Vec3::splat(SCALE * stats.size);
+    create_sprite_sheet(commands, transform, creature_texture_handle, index)
+        .with(TextureAtlasSprite {
+            index,
+            color: kind.tint(),
+        })
+        .with_bundle(CreatureBundle::new(kind));
+    if stats.pass_boxes {
+        commands.with(WallPass);
+    }
+    if stats.size > 1.0 {
+        commands.with(Footprint(stats.size));
+    }
+    if kind == CreatureKind::Boss {
+        commands.with(Boss::default());
+    }
+    commands
 }
 pub fn create_bomb(
     commands: &mut Commands,
@@ -211,6 +312,7 @@ pub fn create_ember(
     texture_handle: Handle<TextureAtlas>,
     direction: Direction,
     is_end: bool,
+    blast: Blast,
 ) {
     let index = match (direction, is_end) {
         (Direction::Left, true) => 0,
@@ -221,16 +323,21 @@ pub fn create_ember(
         (Direction::Up, false) | (Direction::Down, false) => 5,
     };
     let transform = create_transform(translation, OBJECT_LAYER + 3.0);
-    create_sprite_sheet(commands, transform, texture_handle, index).with(Fire::ember());
+    create_sprite_sheet(commands, transform, texture_handle, index)
+        .with(Fire::ember())
+        .with(blast);
 }
 pub fn create_center_fire(
     commands: &mut Commands,
     translation: Vec2,
     texture_handle: Handle<TextureAtlas>,
     power: i32,
+    blast: Blast,
 ) {
     let transform = create_transform(translation, OBJECT_LAYER + 3.0);
-    create_sprite_sheet(commands, transform, texture_handle, 8).with_bundle(FireBundle::new(power));
+    create_sprite_sheet(commands, transform, texture_handle, 8)
+        .with_bundle(FireBundle::new(power))
+        .with(blast);
 }
 
 pub fn create_power_buff(
@@ -254,11 +361,47 @@ pub fn create_bomb_number_buff(
 ) {
     create_buff(commands, translation, material_handle, 1.25).with(Buff::BombNumberBuff);
 }
+pub fn create_shield_buff(
+    commands: &mut Commands,
+    translation: Vec3,
+    material_handle: Handle<ColorMaterial>,
+) {
+    create_buff(commands, translation, material_handle, 0.5).with(Buff::ShieldBuff);
+}
+pub fn create_wall_pass_buff(
+    commands: &mut Commands,
+    translation: Vec3,
+    material_handle: Handle<ColorMaterial>,
+) {
+    create_buff(commands, translation, material_handle, 0.5).with(Buff::WallPassBuff);
+}
+pub fn create_bomb_pass_buff(
+    commands: &mut Commands,
+    translation: Vec3,
+    material_handle: Handle<ColorMaterial>,
+) {
+    create_buff(commands, translation, material_handle, 0.5).with(Buff::BombPassBuff);
+}
 pub fn create_portal(
     commands: &mut Commands,
     translation: Vec3,
     texture_handle: Handle<TextureAtlas>,
 ) {
     let transform = create_transform_vec3(translation);
-    create_sprite_sheet(commands, transform, texture_handle, 1).with(Portal);
+    // like a buff, the portal must survive the blast that revealed it
+    create_sprite_sheet(commands, transform, texture_handle, 1)
+        .with(Portal)
+        .with(PortalWaves::default())
+        .with(Destructible::ExitPortal)
+        .with(Invulnerable::new(FIRE_LIFETIME));
+}
+pub fn create_burning_buff(
+    commands: &mut Commands,
+    translation: Vec3,
+    texture_handle: Handle<TextureAtlas>,
+) {
+    let transform = create_transform(translation.truncate(), OBJECT_LAYER + 3.0);
+    create_sprite_sheet(commands, transform, texture_handle, 8)
+        .with(Burning)
+        .with(Animation(Timer::from_seconds(FIRE_ANIMATE_TIME, true)));
 }
@@ -1,15 +1,38 @@
 use crate::{
-    components::{BombNumber, Player},
-    resources::Map,
+    battle::Outcome,
+    components::{Blast, BombNumber, Collected, GameMode, Player},
+    controls::{Action, Actions},
+    creatures::CreatureKind,
+    resources::{Difficulty, Map},
     state::*,
 };
 use anyhow::Result;
 use bevy::{app::AppExit, prelude::*};
 use bevy_rapier2d::physics::RapierConfiguration;
+// What killed a player.
+#[derive(Clone, Copy, Debug)]
+pub enum DeathCause {
+    // the blast says whose bomb it was
+    Fire(Blast),
+    Creature(CreatureKind),
+    // crushed by a wall closing in during sudden death
+    Hazard,
+}
 pub enum GameEvents {
     GameOver,
     Victory,
+    // a versus round is decided, the results are shown either way
+    RoundOver(Outcome),
     RecoveryBombNumber(Entity),
+    ScatterItems(Collected),
+    PlayerHit(Entity, DeathCause),
+    // the death animation is over, a life is lost
+    PlayerDied(Entity, DeathCause),
+    CreatureHit(Entity, Blast),
+    PortalHit(Entity),
+    BoxDestroyed(Blast),
+    CreatureKilled(CreatureKind, Blast),
+    ItemCollected(Entity),
 }
 pub fn game_events_handle(
     game_events: Res<Events<GameEvents>>,
@@ -27,7 +50,7 @@ pub fn game_events_handle(
                     game_state.set_next(GameState::GameOver)?
                 }
             },
-            GameEvents::Victory => match game_state.current() {
+            GameEvents::Victory | GameEvents::RoundOver(_) => match game_state.current() {
                 GameState::GameOver | GameState::Victory | GameState::Invalid => {}
                 _ => {
                     physics_state.physics_pipeline_active = false;
@@ -42,6 +65,14 @@ pub fn game_events_handle(
                     }
                 }
             }
+            GameEvents::ScatterItems(_)
+            | GameEvents::PlayerHit(..)
+            | GameEvents::PlayerDied(..)
+            | GameEvents::CreatureHit(..)
+            | GameEvents::PortalHit(_)
+            | GameEvents::BoxDestroyed(_)
+            | GameEvents::CreatureKilled(..)
+            | GameEvents::ItemCollected(_) => {}
         }
     }
     Ok(())
@@ -51,21 +82,49 @@ pub fn jump_state(
     mut app_state: ResMut<State<AppState>>,
     mut game_state: ResMut<State<GameState>>,
     mut map: ResMut<Map>,
+    mut difficulty: ResMut<Difficulty>,
+    mut game_mode: ResMut<GameMode>,
+    runstate: Res<RunState>,
+    actions: Res<Actions>,
     input: Res<Input<KeyCode>>,
     mut app_exit_events: ResMut<Events<AppExit>>,
 ) -> Result<()> {
+    let pressed = |action| actions.any_just_pressed(action);
+    // the first player's movement also drives the menus
+    let menu_pressed = |action| actions.just_pressed(0, action);
     match app_state.current() {
         AppState::StartMenu => {
-            if input.just_pressed(KeyCode::Return) {
-                app_state.set_next(AppState::Game)?;
-                game_state.set_next(GameState::Game)?;
+            // everyone picks a character before the game starts
+            if pressed(Action::Confirm) {
+                app_state.set_next(AppState::CharacterSelect)?;
             }
-            if input.just_pressed(KeyCode::Escape) {
+            if pressed(Action::Pause) {
                 app_exit_events.send(AppExit);
             }
+            // the menu shortcuts are fixed keys, they are not part of anyone's bindings
+            if input.just_pressed(KeyCode::V) {
+                map.versus();
+                app_state.set_next(AppState::CharacterSelect)?;
+            }
+            if input.just_pressed(KeyCode::C) {
+                app_state.set_next(AppState::Controls)?;
+            }
+            if menu_pressed(Action::MoveLeft) {
+                *difficulty = difficulty.previous();
+            }
+            if menu_pressed(Action::MoveRight) {
+                *difficulty = difficulty.next();
+            }
+            if menu_pressed(Action::MoveUp) {
+                *game_mode = GameMode::with_players(game_mode.player_count() + 1);
+            }
+            if menu_pressed(Action::MoveDown) {
+                *game_mode = GameMode::with_players(game_mode.player_count() - 1);
+            }
         }
+        AppState::Controls | AppState::CharacterSelect => {}
         AppState::Game => {
-            if input.just_pressed(KeyCode::Back) {
+            if pressed(Action::Back) {
                 app_state.set_next(AppState::StartMenu)?;
                 game_state.set_next(GameState::Invalid)?;
                 map.init();
@@ -73,32 +132,38 @@ pub fn jump_state(
             match game_state.current() {
                 GameState::Invalid => {}
                 GameState::Game => {
-                    if input.just_pressed(KeyCode::Escape) {
+                    if pressed(Action::Pause) {
                         game_state.set_next(GameState::Pause)?;
                     }
                 }
                 GameState::Pause => {
-                    if input.just_pressed(KeyCode::Escape) {
+                    if pressed(Action::Pause) {
                         game_state.set_next(GameState::Game)?;
                     }
                 }
                 GameState::GameOver => {
-                    if input.just_pressed(KeyCode::Return) {
+                    if pressed(Action::Confirm) {
                         app_state.set_next(AppState::StartMenu)?;
                         game_state.set_next(GameState::Invalid)?;
                         map.init();
                     }
-                    if input.just_pressed(KeyCode::Escape) {
+                    if pressed(Action::Pause) {
                         app_exit_events.send(AppExit);
                     }
                 }
                 GameState::Victory => {
-                    if input.just_pressed(KeyCode::Return) {
+                    let match_over =
+                        map.rules.last_standing_wins && runstate.battle.champion().is_some();
+                    if pressed(Action::Confirm) && match_over {
+                        app_state.set_next(AppState::StartMenu)?;
+                        game_state.set_next(GameState::Invalid)?;
+                        map.init();
+                    } else if pressed(Action::Confirm) {
                         map.next();
                         app_state.set_next(AppState::Temporary)?;
                         game_state.set_next(GameState::Game)?;
                     }
-                    if input.just_pressed(KeyCode::Escape) {
+                    if pressed(Action::Pause) {
                         app_exit_events.send(AppExit);
                     }
                 }
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+
+use crate::{components::MAX_PLAYERS, controls::Action};
+
+// Sticks have to be pushed at least this far to count as a direction.
+const STICK_DEADZONE: f32 = 0.5;
+
+// Pads are not remappable, they all share this layout.
+const PAD_BUTTONS: [(GamepadButtonType, Action); 9] = [
+    (GamepadButtonType::DPadUp, Action::MoveUp),
+    (GamepadButtonType::DPadDown, Action::MoveDown),
+    (GamepadButtonType::DPadLeft, Action::MoveLeft),
+    (GamepadButtonType::DPadRight, Action::MoveRight),
+    (GamepadButtonType::South, Action::PlaceBomb),
+    (GamepadButtonType::South, Action::Confirm),
+    (GamepadButtonType::East, Action::Detonate),
+    (GamepadButtonType::Start, Action::Pause),
+    (GamepadButtonType::Select, Action::Back),
+];
+const PAD_STICKS: [(GamepadAxisType, GamepadAxisType); 2] = [
+    (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+    // some pads report the d-pad as a pair of axes
+    (GamepadAxisType::DPadX, GamepadAxisType::DPadY),
+];
+
+// Which pad plays which slot, a pad takes the first free slot when it connects.
+pub struct PadSlots(pub Vec<Option<Gamepad>>);
+
+impl Default for PadSlots {
+    fn default() -> Self {
+        Self(vec![None; MAX_PLAYERS])
+    }
+}
+
+impl PadSlots {
+    pub fn get(&self, slot: usize) -> Option<Gamepad> {
+        self.0.get(slot).copied().flatten()
+    }
+    fn connect(&mut self, pad: Gamepad) {
+        if self.0.contains(&Some(pad)) {
+            return;
+        }
+        if let Some(free) = self.0.iter_mut().find(|slot| slot.is_none()) {
+            *free = Some(pad);
+        }
+    }
+    fn disconnect(&mut self, pad: Gamepad) {
+        for slot in self.0.iter_mut().filter(|slot| **slot == Some(pad)) {
+            *slot = None;
+        }
+    }
+}
+
+pub fn assign_gamepads(
+    gamepad_events: Res<Events<GamepadEvent>>,
+    mut events_reader: Local<EventReader<GamepadEvent>>,
+    mut pad_slots: ResMut<PadSlots>,
+) {
+    for GamepadEvent(pad, event_type) in events_reader.iter(&gamepad_events) {
+        match event_type {
+            GamepadEventType::Connected => {
+                pad_slots.connect(*pad);
+                info!("{:?} connected, slots: {:?}", pad, pad_slots.0);
+            }
+            GamepadEventType::Disconnected => {
+                pad_slots.disconnect(*pad);
+                info!("{:?} disconnected, slots: {:?}", pad, pad_slots.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+// The stronger axis wins, nothing inside the deadzone.
+fn stick_action(x: f32, y: f32) -> Option<Action> {
+    if x.abs().max(y.abs()) < STICK_DEADZONE {
+        None
+    } else if x.abs() >= y.abs() {
+        Some(if x < 0.0 {
+            Action::MoveLeft
+        } else {
+            Action::MoveRight
+        })
+    } else if y < 0.0 {
+        Some(Action::MoveDown)
+    } else {
+        Some(Action::MoveUp)
+    }
+}
+
+// The actions a pad is holding down right now.
+pub fn pad_actions(
+    pad: Gamepad,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> Vec<Action> {
+    let mut actions: Vec<Action> = PAD_BUTTONS
+        .iter()
+        .filter(|(button, _)| buttons.pressed(GamepadButton(pad, *button)))
+        .map(|(_, action)| *action)
+        .collect();
+    let axis = |axis_type| axes.get(GamepadAxis(pad, axis_type)).unwrap_or(0.0);
+    for &(x_axis, y_axis) in PAD_STICKS.iter() {
+        if let Some(action) = stick_action(axis(x_axis), axis(y_axis)) {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controls::{update_actions, Actions, Bindings},
+        touch::TouchControls,
+    };
+
+    #[test]
+    fn stick_deadzone_and_dominant_axis() {
+        assert_eq!(stick_action(0.0, 0.0), None);
+        assert_eq!(stick_action(0.4, -0.4), None);
+        assert_eq!(stick_action(0.9, 0.2), Some(Action::MoveRight));
+        assert_eq!(stick_action(-0.6, 0.5), Some(Action::MoveLeft));
+        assert_eq!(stick_action(-0.3, -0.8), Some(Action::MoveDown));
+        assert_eq!(stick_action(0.1, 0.7), Some(Action::MoveUp));
+    }
+
+    #[test]
+    fn pads_take_the_first_free_slot() {
+        let mut pad_slots = PadSlots::default();
+        pad_slots.connect(Gamepad(7));
+        pad_slots.connect(Gamepad(3));
+        // reconnecting does not take a second slot
+        pad_slots.connect(Gamepad(7));
+        assert_eq!(pad_slots.get(0), Some(Gamepad(7)));
+        assert_eq!(pad_slots.get(1), Some(Gamepad(3)));
+        assert_eq!(pad_slots.get(2), None);
+
+        pad_slots.disconnect(Gamepad(7));
+        assert_eq!(pad_slots.get(0), None);
+        assert_eq!(pad_slots.get(1), Some(Gamepad(3)));
+        pad_slots.connect(Gamepad(9));
+        assert_eq!(pad_slots.get(0), Some(Gamepad(9)));
+    }
+
+    fn pad_app() -> AppBuilder {
+        let mut app = App::build();
+        app.add_event::<GamepadEvent>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Bindings>()
+            .init_resource::<PadSlots>()
+            .init_resource::<TouchControls>()
+            .init_resource::<Actions>()
+            // pads are assigned before their input is read, as in the game
+            .add_system_to_stage(stage::PRE_UPDATE, assign_gamepads.system())
+            .add_system(update_actions.system());
+        app
+    }
+
+    fn send(app: &mut AppBuilder, pad: Gamepad, event_type: GamepadEventType) {
+        app.resources_mut()
+            .get_mut::<Events<GamepadEvent>>()
+            .unwrap()
+            .send(GamepadEvent(pad, event_type));
+    }
+
+    #[test]
+    fn hot_plugged_pads_drive_their_slot() {
+        let mut app = pad_app();
+        let first = Gamepad(0);
+        let second = Gamepad(1);
+        send(&mut app, first, GamepadEventType::Connected);
+        send(&mut app, second, GamepadEventType::Connected);
+        app.app.update();
+        assert_eq!(
+            app.resources().get::<PadSlots>().unwrap().get(1),
+            Some(second)
+        );
+
+        app.resources_mut()
+            .get_mut::<Input<GamepadButton>>()
+            .unwrap()
+            .press(GamepadButton(second, GamepadButtonType::South));
+        app.resources_mut()
+            .get_mut::<Axis<GamepadAxis>>()
+            .unwrap()
+            .set(GamepadAxis(second, GamepadAxisType::LeftStickX), -0.9);
+        app.app.update();
+        {
+            let actions = app.resources().get::<Actions>().unwrap();
+            assert!(actions.pressed(1, Action::PlaceBomb));
+            assert!(actions.pressed(1, Action::MoveLeft));
+            assert!(!actions.pressed(0, Action::PlaceBomb));
+        }
+
+        // unplugged, the pad no longer plays its slot
+        send(&mut app, second, GamepadEventType::Disconnected);
+        app.app.update();
+        let actions = app.resources().get::<Actions>().unwrap();
+        assert!(!actions.pressed(1, Action::PlaceBomb));
+        assert_eq!(app.resources().get::<PadSlots>().unwrap().get(1), None);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    components::{
+        Bomb, BombPower, Buff, Destructible, Direction, Ember, Fire, Player, Portal, Stop, Wall,
+        EMBER_START_TIME,
+    },
+    resources::Map,
+    utils::TILE_WIDTH,
+};
+
+pub trait GridSystems {
+    fn grid_systems(&mut self) -> &mut Self;
+}
+impl GridSystems for SystemStage {
+    fn grid_systems(&mut self) -> &mut Self {
+        self.add_system(update_grid.system())
+            .add_system(update_player_distance.system())
+            .add_system(update_danger_map.system())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Tile {
+    Floor,
+    Wall,
+    Box,
+    Bomb,
+    Item,
+}
+
+// What currently occupies every tile of the map, rebuilt each frame.
+#[derive(Default)]
+pub struct Grid {
+    tiles: Vec<Vec<Tile>>,
+}
+
+impl Grid {
+    pub fn width(&self) -> usize {
+        self.tiles.first().map_or(0, |row| row.len())
+    }
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+    pub fn get(&self, x: usize, y: usize) -> Option<Tile> {
+        self.tiles.get(y).and_then(|row| row.get(x)).copied()
+    }
+    pub fn is_walkable(&self, x: usize, y: usize) -> bool {
+        matches!(self.get(x, y), Some(Tile::Floor) | Some(Tile::Item))
+    }
+    pub fn to_tile(&self, position: Vec2) -> Option<(usize, usize)> {
+        let x = (position.x / TILE_WIDTH).round();
+        let y = self.height() as f32 - 1.0 - (position.y / TILE_WIDTH).round();
+        if x < 0.0 || y < 0.0 || x as usize >= self.width() || y as usize >= self.height() {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+    pub fn to_position(&self, x: usize, y: usize) -> Vec2 {
+        Vec2::new(
+            TILE_WIDTH * x as f32,
+            TILE_WIDTH * (self.height() - y - 1) as f32,
+        )
+    }
+    pub fn free_tiles(&self) -> Vec<(usize, usize)> {
+        let mut tiles = Vec::new();
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile == Tile::Floor {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+    pub fn neighbour(&self, x: usize, y: usize, direction: Direction) -> Option<(usize, usize)> {
+        let (x, y) = match direction {
+            Direction::Left if x > 0 => (x - 1, y),
+            Direction::Up if y > 0 => (x, y - 1),
+            Direction::Right => (x + 1, y),
+            Direction::Down => (x, y + 1),
+            _ => return None,
+        };
+        if x < self.width() && y < self.height() {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+    // The tiles a bomb's fire reaches, fire only stops at indestructible walls.
+    pub fn blast(&self, x: usize, y: usize, power: i32) -> Vec<(usize, usize)> {
+        let mut tiles = vec![(x, y)];
+        for &direction in DIRECTIONS.iter() {
+            let (mut cx, mut cy) = (x, y);
+            for _ in 0..power {
+                match self.neighbour(cx, cy, direction) {
+                    Some((nx, ny)) if self.get(nx, ny) != Some(Tile::Wall) => {
+                        tiles.push((nx, ny));
+                        cx = nx;
+                        cy = ny;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        tiles
+    }
+    fn set(&mut self, position: Vec2, tile: Tile) {
+        if let Some((x, y)) = self.to_tile(position) {
+            self.tiles[y][x] = tile;
+        }
+    }
+}
+
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+];
+
+// Walking distance from every tile to the nearest target, in tiles.
+#[derive(Default)]
+pub struct DistanceField {
+    distances: Vec<Vec<Option<u32>>>,
+}
+
+impl DistanceField {
+    pub fn new(grid: &Grid, targets: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let mut distances = vec![vec![None; grid.width()]; grid.height()];
+        let mut queue = VecDeque::new();
+        for (x, y) in targets {
+            if y < grid.height() && x < grid.width() && distances[y][x].is_none() {
+                distances[y][x] = Some(0);
+                queue.push_back((x, y));
+            }
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            let distance = distances[y][x].unwrap_or(0) + 1;
+            for &direction in DIRECTIONS.iter() {
+                if let Some((nx, ny)) = grid.neighbour(x, y, direction) {
+                    if distances[ny][nx].is_none() && grid.is_walkable(nx, ny) {
+                        distances[ny][nx] = Some(distance);
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+        }
+        Self { distances }
+    }
+    pub fn get(&self, x: usize, y: usize) -> Option<u32> {
+        self.distances
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .flatten()
+    }
+    // The direction that gets one tile closer to a target.
+    pub fn step(&self, grid: &Grid, x: usize, y: usize) -> Option<Direction> {
+        let current = self.get(x, y)?;
+        DIRECTIONS
+            .iter()
+            .filter_map(|&direction| {
+                let (nx, ny) = grid.neighbour(x, y, direction)?;
+                let distance = self.get(nx, ny)?;
+                if distance < current {
+                    Some((distance, direction))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, direction)| direction)
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerDistance(pub DistanceField);
+
+fn update_grid(
+    mut grid: ResMut<Grid>,
+    map: Res<Map>,
+    wall_query: Query<(&Transform, Option<&Destructible>), (With<Wall>, Without<Bomb>)>,
+    bomb_query: Query<&Transform, With<Bomb>>,
+    item_query: Query<&Transform, Or<(With<Buff>, With<Portal>)>>,
+) {
+    grid.tiles = map.iter().map(|row| vec![Tile::Floor; row.len()]).collect();
+    for (transform, destructible) in wall_query.iter() {
+        let tile = match destructible {
+            Some(_) => Tile::Box,
+            None => Tile::Wall,
+        };
+        grid.set(transform.translation.truncate(), tile);
+    }
+    for transform in item_query.iter() {
+        grid.set(transform.translation.truncate(), Tile::Item);
+    }
+    for transform in bomb_query.iter() {
+        grid.set(transform.translation.truncate(), Tile::Bomb);
+    }
+}
+
+fn update_player_distance(
+    grid: Res<Grid>,
+    mut player_distance: ResMut<PlayerDistance>,
+    player_query: Query<&Transform, (With<Player>, Without<Stop>)>,
+) {
+    let targets = player_query
+        .iter()
+        .filter_map(|transform| grid.to_tile(transform.translation.truncate()));
+    player_distance.0 = DistanceField::new(&grid, targets);
+}
+
+// Seconds until fire reaches each tile; zero means it is burning right now.
+#[derive(Default)]
+pub struct DangerMap {
+    fuses: Vec<Vec<Option<f32>>>,
+    safe_distance: DistanceField,
+}
+
+impl DangerMap {
+    pub fn get(&self, x: usize, y: usize) -> Option<f32> {
+        self.fuses
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .flatten()
+    }
+    pub fn is_dangerous(&self, x: usize, y: usize, margin: f32) -> bool {
+        self.get(x, y).map_or(false, |fuse| fuse <= margin)
+    }
+    // The first step towards the nearest tile no blast will reach.
+    pub fn escape(&self, grid: &Grid, x: usize, y: usize) -> Option<Direction> {
+        self.safe_distance.step(grid, x, y)
+    }
+    fn mark(&mut self, x: usize, y: usize, fuse: f32) {
+        let tile = &mut self.fuses[y][x];
+        *tile = Some(tile.map_or(fuse, |old| old.min(fuse)));
+    }
+    fn mark_blast(&mut self, grid: &Grid, x: usize, y: usize, power: i32, fuse: f32) {
+        self.mark(x, y, fuse);
+        for (bx, by) in grid.blast(x, y, power).into_iter().skip(1) {
+            self.mark(bx, by, fuse + EMBER_START_TIME);
+        }
+    }
+}
+
+fn update_danger_map(
+    grid: Res<Grid>,
+    mut danger_map: ResMut<DangerMap>,
+    bomb_query: Query<(&Transform, &Bomb, &BombPower)>,
+    fire_query: Query<(&Transform, Option<&Ember>), With<Fire>>,
+) {
+    danger_map.fuses = vec![vec![None; grid.width()]; grid.height()];
+    for (transform, bomb, power) in bomb_query.iter() {
+        if let Some((x, y)) = grid.to_tile(transform.translation.truncate()) {
+            let fuse = (bomb.timer.duration() - bomb.timer.elapsed()).max(0.0);
+            danger_map.mark_blast(&grid, x, y, power.0, fuse);
+        }
+    }
+    for (transform, ember) in fire_query.iter() {
+        if let Some((x, y)) = grid.to_tile(transform.translation.truncate()) {
+            match ember {
+                // the centre of a blast that has yet to spread
+                Some(ember) if !ember.0.finished() => {
+                    let fuse = (ember.0.duration() - ember.0.elapsed()).max(0.0);
+                    danger_map.mark_blast(&grid, x, y, ember.1, fuse - EMBER_START_TIME);
+                    danger_map.mark(x, y, 0.0);
+                }
+                _ => danger_map.mark(x, y, 0.0),
+            }
+        }
+    }
+    let mut safe_tiles = Vec::new();
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            if grid.is_walkable(x, y) && danger_map.get(x, y).is_none() {
+                safe_tiles.push((x, y));
+            }
+        }
+    }
+    danger_map.safe_distance = DistanceField::new(&grid, safe_tiles);
+}
@@ -5,30 +5,43 @@ use bevy_rapier2d::{
     rapier::math::Vector,
 };
 
-use components::{AnimateIndexs, Player};
-use creatures::Creature;
+use characters::{CharacterSelect, Characters};
+use components::GameMode;
+use controls::{update_actions, Actions, Bindings, ControlsMenu};
 use errors::error_handler;
 use events::{game_events_handle, jump_state, GameEvents};
-use resources::{Map, MAX_HEIGHT, MAX_WIDTH};
+use gamepad::{assign_gamepads, PadSlots};
+use grid::{DangerMap, Grid, PlayerDistance};
+use resources::{CornerAssist, Difficulty, Map, MAX_HEIGHT, MAX_WIDTH};
 use state::*;
+use touch::{touch_actions, TouchControls};
 use ui::{draw_blink_system, ButtonMaterials};
 use utils::TILE_WIDTH;
 
 pub mod assets;
+pub mod battle;
+pub mod behaviour;
 pub mod bomb;
+pub mod bot;
 pub mod buff;
+pub mod characters;
 pub mod components;
 pub mod constants;
+pub mod controls;
 pub mod creatures;
 pub mod entities;
 pub mod errors;
 pub mod events;
+pub mod gamepad;
+pub mod grid;
 pub mod physics;
 pub mod player;
 pub mod portal;
 pub mod resources;
+pub mod score;
 pub mod setup_map;
 pub mod state;
+pub mod touch;
 pub mod ui;
 pub mod utils;
 
@@ -50,16 +63,35 @@ fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     app.add_plugins(DefaultPlugins);
     #[cfg(target_arch = "wasm32")]
-    app.add_plugins(bevy_webgl2::DefaultPlugins);
+    app.add_plugins(bevy_webgl2::DefaultPlugins)
+        .init_resource::<touch::web::WebTouches>()
+        .add_startup_system(touch::web::listen_web_touches.system())
+        // before bevy turns touch events into `Touches`
+        .add_system_to_stage(stage::PRE_EVENT, touch::web::forward_web_touches.system());
     app.add_resource(Map::first())
+        .init_resource::<Difficulty>()
+        .init_resource::<GameMode>()
+        .add_resource(Bindings::load())
+        .init_resource::<CornerAssist>()
+        .init_resource::<ControlsMenu>()
+        .init_resource::<Characters>()
+        .init_resource::<CharacterSelect>()
+        .init_resource::<PadSlots>()
+        .init_resource::<Actions>()
+        .init_resource::<TouchControls>()
         .init_resource::<ButtonMaterials>()
-        .add_resource(AnimateIndexs::<Player>::player4())
-        .add_resource(AnimateIndexs::<Creature>::player2())
+        .init_resource::<Grid>()
+        .init_resource::<PlayerDistance>()
+        .init_resource::<DangerMap>()
         .add_event::<GameEvents>()
         .add_plugin(AppStatePluge)
         .add_plugin(GameStatePlugin)
         .add_plugin(RapierPhysicsPlugin)
         .add_startup_system(setup.system())
+        // input is read once a frame, before anything acts on it
+        .add_system_to_stage(stage::PRE_UPDATE, assign_gamepads.system())
+        .add_system_to_stage(stage::PRE_UPDATE, touch_actions.system())
+        .add_system_to_stage(stage::PRE_UPDATE, update_actions.system())
         .add_system(draw_blink_system.system())
         .add_system(game_events_handle.system().chain(error_handler.system()))
         .add_system(jump_state.system().chain(error_handler.system()))
@@ -142,6 +174,12 @@ fn setup(
         ))
         .insert_resource(LifeMaterial(
             materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+        ))
+        .insert_resource(WallPassBuffMaterial(
+            materials.add(Color::rgb(0.6, 0.4, 0.2).into()),
+        ))
+        .insert_resource(BombPassBuffMaterial(
+            materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
         ));
     commands.insert_resource(RunState::new(&asset_server));
 }
@@ -8,13 +8,12 @@ use bevy_rapier2d::{
         geometry::{ColliderBuilder, ColliderSet, ContactEvent::Started, InteractionGroups},
     },
 };
-use rand::{thread_rng, Rng};
 
 use crate::{
     components::{Direction, *},
-    creatures::{Creature, StopAndFlashing},
+    creatures::{Creature, CreatureKind},
     errors::error_handler,
-    events::GameEvents,
+    events::{DeathCause, GameEvents},
     utils::HALF_TILE_WIDTH,
 };
 
@@ -22,7 +21,11 @@ const CREATURE_GROUPS: u16 = 0b0010;
 const PLAYER_GROUPS: u16 = 0b0001;
 const WALL_GROUPS: u16 = 0b0100;
 const WAY_GROUPS: u16 = 0b1000;
+const BOX_GROUPS: u16 = 0b1_0000;
+const BOMB_GROUPS: u16 = 0b10_0000;
 const NONE_GROUPS: u16 = 0b0000;
+const ALL_GROUPS: u16 = u16::MAX;
+const BLOCK_GROUPS: u16 = WALL_GROUPS | BOX_GROUPS | BOMB_GROUPS;
 
 pub trait PhysicsSystems {
     fn physics_systems(&mut self) -> &mut Self;
@@ -39,7 +42,7 @@ impl PhysicsSystems for SystemStage {
 fn for_player_add_collision_detection(
     commands: &mut Commands,
     query: Query<
-        (Entity, &Transform),
+        (Entity, &Transform, Option<&WallPass>, Option<&BombPass>),
         (
             With<Player>,
             Without<RigidBodyBuilder>,
@@ -49,21 +52,33 @@ fn for_player_add_collision_detection(
         ),
     >,
 ) {
-    for (entity, transform) in query.iter() {
+    for (entity, transform, wall_pass, bomb_pass) in query.iter() {
         let translation = transform.translation;
+        let mut filter = BLOCK_GROUPS;
+        if wall_pass.is_some() {
+            filter &= !BOX_GROUPS;
+        }
+        if bomb_pass.is_some() {
+            filter &= !BOMB_GROUPS;
+        }
         commands.insert(
             entity,
             (
                 create_dyn_rigid_body(translation.x, translation.y),
-                create_player_collider(entity),
+                create_player_collider(entity, filter),
             ),
         );
     }
 }
+// Solver groups are fixed once a collider is built, so a player whose passes change
+// drops the old body and `for_player_add_collision_detection` builds one with the new filter.
+pub fn rebuild_player_body(commands: &mut Commands, entity: Entity) {
+    commands.remove::<(RigidBodyHandleComponent, ColliderHandleComponent)>(entity);
+}
 fn for_wall_add_collision_detection(
     commands: &mut Commands,
     query: Query<
-        (Entity, &Transform),
+        (Entity, &Transform, Option<&Destructible>, Option<&Bomb>),
         (
             With<Wall>,
             Without<RigidBodyBuilder>,
@@ -73,13 +88,18 @@ fn for_wall_add_collision_detection(
         ),
     >,
 ) {
-    for (entity, transform) in query.iter() {
+    for (entity, transform, destructible, bomb) in query.iter() {
         let translation = transform.translation;
+        let groups = match (destructible, bomb) {
+            (_, Some(_)) => BOMB_GROUPS,
+            (Some(_), None) => BOX_GROUPS,
+            (None, None) => WALL_GROUPS,
+        };
         commands.insert(
             entity,
             (
                 create_static_rigid_body(translation.x, translation.y),
-                create_collider(entity),
+                create_collider(entity).solver_groups(InteractionGroups::new(groups, ALL_GROUPS)),
             ),
         );
     }
@@ -111,7 +131,7 @@ fn for_way_add_collision_detection(
 fn for_creature_add_collision_detection(
     commands: &mut Commands,
     query: Query<
-        (Entity, &Transform),
+        (Entity, &Transform, Option<&WallPass>, Option<&Footprint>),
         (
             With<Creature>,
             Without<RigidBodyBuilder>,
@@ -121,13 +141,22 @@ fn for_creature_add_collision_detection(
         ),
     >,
 ) {
-    for (entity, transform) in query.iter() {
+    for (entity, transform, wall_pass, footprint) in query.iter() {
         let translation = transform.translation;
+        let filter = if wall_pass.is_some() {
+            BLOCK_GROUPS & !BOX_GROUPS
+        } else {
+            BLOCK_GROUPS
+        };
         commands.insert(
             entity,
             (
                 create_dyn_rigid_body(translation.x, translation.y),
-                create_creature_collider(entity),
+                create_creature_collider(
+                    entity,
+                    filter,
+                    footprint.map_or(1.0, |footprint| footprint.0),
+                ),
             ),
         );
     }
@@ -143,24 +172,21 @@ pub fn get_velocity_vec(direction: &Direction, velocity: f32) -> Vector2<f32> {
     }
 }
 fn handle_contact_events(
-    commands: &mut Commands,
     events: Res<EventQueue>,
     collider_set: Res<ColliderSet>,
     //query_pipeline: Res<QueryPipeline>,
-    mut game_over_events: ResMut<Events<GameEvents>>,
+    mut game_events: ResMut<Events<GameEvents>>,
     //mut rigid_body_handle_query: Query<&mut RigidBodyHandleComponent>,
     //mut rigid_body_set: ResMut<RigidBodySet>,
-    mut query_set: QuerySet<(
-        Query<
-            (Option<&Player>, Option<&Direction>),
-            (
-                Or<(With<Player>, With<Creature>, With<Wall>)>,
-                Without<Stop>,
-            ),
-        >,
-        Query<&mut Direction>,
-    )>,
+    query: Query<
+        (Option<&Player>, Option<&CreatureKind>),
+        (
+            Or<(With<Player>, With<Creature>, With<Wall>)>,
+            Without<Stop>,
+        ),
+    >,
 ) -> Result<()> {
+    // creatures steer by the tile grid, so only player hits matter here
     while let Ok(contact_event) = events.contact_events.pop() {
         match contact_event {
             Started(idxl, idxr) => {
@@ -176,69 +202,23 @@ fn handle_contact_events(
                         .ok_or(anyhow!("get error!"))?
                         .user_data as u64,
                 );
-                match query_set.q0().get(entity_left) {
-                    Ok((Some(_), Some(_))) => {
-                        // player
-                        match query_set.q0().get(entity_right) {
-                            Ok((None, Some(_))) => {
-                                // creature
-                                commands.insert(entity_left, StopAndFlashing::default());
-                                game_over_events.send(GameEvents::GameOver);
-                            }
-                            Ok(_) => {}
-                            Err(err) => {
-                                error!("{:?}", err);
-                            }
-                        }
+                match (query.get(entity_left), query.get(entity_right)) {
+                    (Ok((Some(_), _)), Ok((None, Some(kind)))) => {
+                        // player and creature
+                        game_events.send(GameEvents::PlayerHit(
+                            entity_left,
+                            DeathCause::Creature(*kind),
+                        ));
                     }
-                    Ok((None, Some(_))) => {
-                        // creature
-                        match query_set.q0().get(entity_right) {
-                            Ok((None, None)) => {
-                                //wall
-                                //TODO:Make creatures smarter
-                                if let Ok(mut direction) = query_set.q1_mut().get_mut(entity_left) {
-                                    let mut rng = thread_rng();
-                                    let n = rng.gen_range(0..=100);
-                                    if n <= 50 {
-                                        // only change ocassionally
-                                        *direction = rand::random();
-                                    }
-                                }
-                            }
-                            Ok((Some(_), Some(_))) => {
-                                commands.insert(entity_left, StopAndFlashing::default());
-                                game_over_events.send(GameEvents::GameOver);
-                            }
-                            Ok(_) => {}
-                            Err(err) => {
-                                error!("{:?}", err);
-                            }
-                        }
+                    (Ok((None, Some(kind))), Ok((Some(_), _))) => {
+                        // creature and player
+                        game_events.send(GameEvents::PlayerHit(
+                            entity_right,
+                            DeathCause::Creature(*kind),
+                        ));
                     }
-                    Ok((None, None)) => {
-                        // wall
-                        match query_set.q0().get(entity_right) {
-                            Ok((None, Some(_))) => {
-                                //TODO:Make creatures smarter
-                                if let Ok(mut direction) = query_set.q1_mut().get_mut(entity_right)
-                                {
-                                    let mut rng = thread_rng();
-                                    let n = rng.gen_range(0..=100);
-                                    if n <= 50 {
-                                        // only change ocassionally
-                                        *direction = rand::random();
-                                    }
-                                }
-                            }
-                            Ok(_) => {}
-                            Err(err) => {
-                                error!("{:?}", err);
-                            }
-                        }
-                    }
-                    Ok(_) => {}
-                    Err(err) => {
+                    (Ok(_), Ok(_)) => {}
+                    (Err(err), _) | (_, Err(err)) => {
                         error!("error:{:?}", err);
                     }
                 }
@@ -268,14 +248,14 @@ pub fn create_collider(entity: Entity) -> ColliderBuilder {
 }
 
 #[inline(always)]
-pub fn create_creature_collider(entity: Entity) -> ColliderBuilder {
+pub fn create_creature_collider(entity: Entity, filter: u16, size: f32) -> ColliderBuilder {
     //ColliderBuilder::cuboid(HALF_TILE_WIDTH, HALF_TILE_WIDTH)
 
-    ColliderBuilder::ball(HALF_TILE_WIDTH)
+    ColliderBuilder::ball(HALF_TILE_WIDTH * size)
         .friction(0.0)
         .restitution(0.0)
         .user_data(entity.to_bits() as u128)
-        .solver_groups(InteractionGroups::new(CREATURE_GROUPS, WALL_GROUPS))
+        .solver_groups(InteractionGroups::new(CREATURE_GROUPS, filter))
 }
 #[inline(always)]
 pub fn create_way_collider(entity: Entity) -> ColliderBuilder {
@@ -301,14 +281,14 @@ pub fn create_ball_collider(entity: Entity) -> ColliderBuilder {
         .user_data(entity.to_bits() as u128)
 }
 #[inline(always)]
-pub fn create_player_collider(entity: Entity) -> ColliderBuilder {
+pub fn create_player_collider(entity: Entity, filter: u16) -> ColliderBuilder {
     //ColliderBuilder::cuboid(HALF_TILE_WIDTH, HALF_TILE_WIDTH)
     ColliderBuilder::ball(HALF_TILE_WIDTH)
         .friction(0.0)
         .restitution(0.0)
         .user_data(entity.to_bits() as u128)
         //.sensor(true)
-        .solver_groups(InteractionGroups::new(PLAYER_GROUPS, WALL_GROUPS))
+        .solver_groups(InteractionGroups::new(PLAYER_GROUPS, filter))
 }
 
 #[inline(always)]
@@ -1,15 +1,24 @@
 use crate::{
     components::{
-        AnimateIndexs, Animation, BombNumber, BombPower, Destructible, Direction, Player, Stop,
-        Velocity,
+        AnimateIndexs, Animation, BombNumber, BombPower, Collected, Destructible, Direction, Human,
+        Intent, Invulnerable, Lives, Player, Shield, SpawnPoint, Stop, Velocity,
     },
+    controls::{Action, Actions},
     errors::querr_error_handler,
+    events::{DeathCause, GameEvents},
+    grid::{Grid, Tile},
+    resources::{CornerAssist, Map},
+    state::RunState,
+    ui::DrawBlinkTimer,
+    utils::lanes_around,
 };
 
 use anyhow::Result;
 use bevy::{ecs::QueryError, prelude::*};
 use bevy_rapier2d::{
-    na::Vector2, physics::RigidBodyHandleComponent, rapier::dynamics::RigidBodySet,
+    na::{Isometry2, Vector2},
+    physics::RigidBodyHandleComponent,
+    rapier::dynamics::RigidBodySet,
 };
 
 pub trait PlayerSystems {
@@ -19,13 +28,32 @@ impl PlayerSystems for SystemStage {
     fn player_systems(&mut self) -> &mut Self {
         self
             // movement
+            .add_system(input_intent.system())
             .add_system(movement.system().chain(querr_error_handler.system()))
             .add_system(stop_player.system())
+            .add_system(player_hit.system())
+            .add_system(animate_death.system())
+            .add_system(player_died.system())
             // animate
             .add_system(animate_player.system())
             .add_system(velocity_to_animation.system())
     }
 }
+const SHIELD_INVULNERABLE_TIME: f32 = 2.0;
+const RESPAWN_INVULNERABLE_TIME: f32 = 3.0;
+const DEATH_TIME: f32 = 1.5;
+const DEATH_SPIN_TIME: f32 = 0.1;
+// Closer than this to a lane centre counts as lined up.
+const LANE_ALIGNED: f32 = 0.5;
+// How long a turn pressed before an intersection waits for the way to open.
+const INPUT_BUFFER_TIME: f32 = 0.2;
+const DIRECTION_ACTIONS: [(Action, Direction); 4] = [
+    (Action::MoveUp, Direction::Up),
+    (Action::MoveDown, Direction::Down),
+    (Action::MoveLeft, Direction::Left),
+    (Action::MoveRight, Direction::Right),
+];
+
 #[derive(Bundle)]
 pub struct PlayerBundle {
     player: Player,
@@ -33,7 +61,10 @@ pub struct PlayerBundle {
     velocity: Velocity,
     bomb_power: BombPower,
     bomb_number: BombNumber,
+    collected: Collected,
+    intent: Intent,
     animation: Animation,
+    animate_indexs: AnimateIndexs<Player>,
     destructible: Destructible,
 }
 
@@ -45,48 +76,154 @@ impl Default for PlayerBundle {
             velocity: Velocity(150.0),
             bomb_power: BombPower(1),
             bomb_number: BombNumber { max: 1, current: 0 },
+            collected: Collected::default(),
+            intent: Intent::default(),
             animation: Animation(Timer::from_seconds(0.3, true)),
+            animate_indexs: AnimateIndexs::player4(),
             destructible: Destructible::Player,
         }
     }
 }
 
+impl PlayerBundle {
+    pub fn new(animate_indexs: AnimateIndexs<Player>) -> Self {
+        Self {
+            animate_indexs,
+            ..Default::default()
+        }
+    }
+}
+
+// The directions a player holds down, in the order they were pressed.
+#[derive(Default)]
+pub struct HeldDirections(pub Vec<Direction>);
+
+fn input_intent(
+    actions: Res<Actions>,
+    mut query: Query<(&Human, &mut HeldDirections, &mut Intent), Without<Stop>>,
+) {
+    for (human, mut held, mut intent) in query.iter_mut() {
+        for &(action, direction) in DIRECTION_ACTIONS.iter() {
+            if actions.just_pressed(human.0, action) {
+                held.0.retain(|&other| other != direction);
+                held.0.push(direction);
+                intent.buffered = Some((direction, INPUT_BUFFER_TIME));
+            } else if !actions.pressed(human.0, action) {
+                held.0.retain(|&other| other != direction);
+            }
+        }
+        // the last pressed wins, then whatever is still held
+        intent.direction = held.0.last().copied();
+        // kept until a bomb is placed, whichever system runs first
+        intent.bomb |= actions.just_pressed(human.0, Action::PlaceBomb);
+        intent.detonate |= actions.just_pressed(human.0, Action::Detonate);
+    }
+}
+
+// Heading along `direction`, how far across to slide to line up with an open lane within `tolerance`.
+fn corner_assist(
+    grid: &Grid,
+    tolerance: f32,
+    position: Vec2,
+    direction: Direction,
+) -> Option<Vec2> {
+    let vertical = matches!(direction, Direction::Up | Direction::Down);
+    let across = if vertical { position.x } else { position.y };
+    let (below, above) = lanes_around(across);
+    let lane = [below, above]
+        .iter()
+        .copied()
+        .filter(|lane| (lane - across).abs() <= tolerance)
+        .find(|&lane| {
+            let centre = if vertical {
+                Vec2::new(lane, position.y)
+            } else {
+                Vec2::new(position.x, lane)
+            };
+            grid.to_tile(centre)
+                .and_then(|(x, y)| grid.neighbour(x, y, direction))
+                .map_or(false, |(x, y)| grid.is_walkable(x, y))
+        })?;
+    Some(if vertical {
+        Vec2::new(lane - across, 0.0)
+    } else {
+        Vec2::new(0.0, lane - across)
+    })
+}
+
 fn movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(Entity, &Velocity, &mut Direction, &mut Player), Without<Stop>>,
+    time: Res<Time>,
+    grid: Res<Grid>,
+    corner_assist_config: Res<CornerAssist>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &mut Intent,
+            &mut Direction,
+            &mut Player,
+        ),
+        Without<Stop>,
+    >,
     mut rigid_body_handle_query: Query<&mut RigidBodyHandleComponent>,
     mut rigid_body_set: ResMut<RigidBodySet>,
 ) -> Result<(), QueryError> {
-    for (entity, velocity, mut direction, mut player) in query.iter_mut() {
-        let movement_action = if keyboard_input.pressed(KeyCode::Left) {
-            //info!("left");
-            Some(Direction::Left)
-        } else if keyboard_input.pressed(KeyCode::Down) {
-            //info!("down");
-            Some(Direction::Down)
-        } else if keyboard_input.pressed(KeyCode::Up) {
-            //info!("up");
-            Some(Direction::Up)
-        } else if keyboard_input.pressed(KeyCode::Right) {
-            //info!("right");
-            Some(Direction::Right)
-        } else {
-            //println!("none");
-            None
-        };
-
+    let delta = time.delta_seconds();
+    for (entity, transform, velocity, mut intent, mut direction, mut player) in query.iter_mut() {
         let rigid_body_handle =
             rigid_body_handle_query.get_component_mut::<RigidBodyHandleComponent>(entity)?;
-        let linvel = match movement_action {
+        let position = transform.translation.truncate();
+        let assist = |dir| corner_assist(&grid, corner_assist_config.tolerance, position, dir);
+        // a buffered turn is taken once the player gets to the lane on their own this frame,
+        // however small the assist tolerance is set
+        let reach = corner_assist_config
+            .tolerance
+            .max(velocity.0 * delta)
+            .max(LANE_ALIGNED);
+        let buffered_assist = |dir| corner_assist(&grid, reach, position, dir);
+        let mut wanted = intent.direction;
+        let mut turning = false;
+        if let Some((turn, time_left)) = intent.buffered {
+            match buffered_assist(turn) {
+                // taken, and kept until lined up with the new lane
+                Some(slide) => {
+                    wanted = Some(turn);
+                    turning = true;
+                    if slide.length() <= LANE_ALIGNED {
+                        intent.buffered = None;
+                    }
+                }
+                // keep going the old way until the turn opens up
+                None if player.is_moving && wanted.is_some() => wanted = Some(*direction),
+                None => {}
+            }
+            if intent.buffered.is_some() {
+                intent.buffered = Some((turn, time_left - delta)).filter(|(_, left)| *left > 0.0);
+            }
+        }
+        let linvel = match wanted {
             Some(dir) => {
                 //info!("pre direction:{:?}",direction);
                 *direction = dir;
                 player.is_moving = true;
-                match dir {
-                    Direction::Left => Vector2::new(-velocity.0, 0.0),
-                    Direction::Up => Vector2::new(0.0, velocity.0),
-                    Direction::Right => Vector2::new(velocity.0, 0.0),
-                    Direction::Down => Vector2::new(0.0, -velocity.0),
+                let slide = if turning {
+                    buffered_assist(dir)
+                } else {
+                    assist(dir)
+                };
+                let slide = slide.filter(|slide| slide.length() > LANE_ALIGNED);
+                match (slide, dir) {
+                    // sideways first, without overshooting the lane
+                    (Some(slide), _) => {
+                        let speed = velocity.0.min(slide.length() / delta.max(f32::EPSILON));
+                        let slide = slide.normalize() * speed;
+                        Vector2::new(slide.x, slide.y)
+                    }
+                    (None, Direction::Left) => Vector2::new(-velocity.0, 0.0),
+                    (None, Direction::Up) => Vector2::new(0.0, velocity.0),
+                    (None, Direction::Right) => Vector2::new(velocity.0, 0.0),
+                    (None, Direction::Down) => Vector2::new(0.0, -velocity.0),
                 }
             }
             None => {
@@ -109,12 +246,17 @@ fn movement(
 // animate
 fn animate_player(
     time: Res<Time>,
-    animate_date: Res<AnimateIndexs<Player>>,
-    mut query: Query<(&mut Animation, &mut TextureAtlasSprite, &Player, &Direction)>,
+    mut query: Query<(
+        &mut Animation,
+        &mut TextureAtlasSprite,
+        &Player,
+        &Direction,
+        &AnimateIndexs<Player>,
+    )>,
 ) {
-    for (mut animation, mut sprite, _, direction) in query
+    for (mut animation, mut sprite, _, direction, animate_date) in query
         .iter_mut()
-        .filter(|(_, _, player, _)| player.is_moving)
+        .filter(|(_, _, player, _, _)| player.is_moving)
     {
         let indexs = match direction {
             Direction::Left => &animate_date.left,
@@ -163,3 +305,188 @@ fn stop_player(mut query: Query<&mut Player, With<Stop>>) {
         player.is_moving = false;
     }
 }
+// Fire and creatures both land here, so a shield absorbs either of them.
+// Nothing saves a player from a hazard.
+fn player_hit(
+    commands: &mut Commands,
+    game_events: Res<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    mut rigid_body_set: ResMut<RigidBodySet>,
+    query: Query<
+        (
+            Option<&Shield>,
+            Option<&Invulnerable>,
+            &RigidBodyHandleComponent,
+        ),
+        (With<Player>, Without<Stop>),
+    >,
+) {
+    let mut hits: Vec<(Entity, DeathCause)> = Vec::new();
+    for event in events_reader.iter(&game_events) {
+        if let GameEvents::PlayerHit(entity, cause) = event {
+            if hits.iter().all(|(hit, _)| hit != entity) {
+                hits.push((*entity, *cause));
+            }
+        }
+    }
+    for (entity, cause) in hits {
+        if let Ok((shield, invulnerable, rigid_body_handle)) = query.get(entity) {
+            let hazard = matches!(cause, DeathCause::Hazard);
+            if invulnerable.is_some() && !hazard {
+                continue;
+            }
+            if shield.is_some() && !hazard {
+                commands.remove_one::<Shield>(entity);
+                commands.insert(
+                    entity,
+                    (
+                        Invulnerable::new(SHIELD_INVULNERABLE_TIME),
+                        DrawBlinkTimer(Timer::from_seconds(0.1, true)),
+                    ),
+                );
+            } else {
+                if let Some(rigid_body) = rigid_body_set.get_mut(rigid_body_handle.handle()) {
+                    rigid_body.set_linvel(Vector2::new(0.0, 0.0), true);
+                }
+                commands.insert(entity, Dying::new(cause));
+            }
+        }
+    }
+}
+
+// A player between the fatal hit and losing the life, input is cut off by `Stop`.
+#[derive(Bundle)]
+pub struct Dying {
+    stop: Stop,
+    blink: DrawBlinkTimer,
+    death: Death,
+}
+pub struct Death {
+    cause: DeathCause,
+    pub timer: Timer,
+    pub spin: Timer,
+}
+impl Dying {
+    fn new(cause: DeathCause) -> Self {
+        Self {
+            stop: Stop,
+            blink: DrawBlinkTimer(Timer::from_seconds(0.2, true)),
+            death: Death {
+                cause,
+                timer: Timer::from_seconds(DEATH_TIME, false),
+                spin: Timer::from_seconds(DEATH_SPIN_TIME, true),
+            },
+        }
+    }
+}
+
+// Spins on the spot, then reports the death.
+fn animate_death(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut game_events: ResMut<Events<GameEvents>>,
+    mut query: Query<(
+        Entity,
+        &mut Death,
+        &mut Direction,
+        &mut TextureAtlasSprite,
+        &AnimateIndexs<Player>,
+    )>,
+) {
+    for (entity, mut death, mut direction, mut sprite, animate_date) in query.iter_mut() {
+        if death.spin.tick(time.delta_seconds()).just_finished() {
+            *direction = match *direction {
+                Direction::Down => Direction::Left,
+                Direction::Left => Direction::Up,
+                Direction::Up => Direction::Right,
+                Direction::Right => Direction::Down,
+            };
+            sprite.index = match *direction {
+                Direction::Left => animate_date.left[0],
+                Direction::Up => animate_date.up[0],
+                Direction::Right => animate_date.right[0],
+                Direction::Down => animate_date.down[0],
+            };
+        }
+        if death.timer.tick(time.delta_seconds()).just_finished() {
+            commands.remove_one::<Death>(entity);
+            game_events.send(GameEvents::PlayerDied(entity, death.cause));
+        }
+    }
+}
+
+// Takes a life, then either brings the player back or takes them out for good.
+fn player_died(
+    commands: &mut Commands,
+    mut game_events: ResMut<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    map: Res<Map>,
+    grid: Res<Grid>,
+    mut runstate: ResMut<RunState>,
+    mut rigid_body_set: ResMut<RigidBodySet>,
+    mut query: Query<(
+        &Collected,
+        Option<&Human>,
+        &mut Lives,
+        &SpawnPoint,
+        &RigidBodyHandleComponent,
+    )>,
+) {
+    let mut deaths = Vec::new();
+    for event in events_reader.iter(&game_events) {
+        if let GameEvents::PlayerDied(entity, cause) = event {
+            info!("{:?} died: {:?}", entity, cause);
+            deaths.push(*entity);
+        }
+    }
+    let mut human_out = false;
+    for entity in deaths {
+        if let Ok((&collected, human, mut lives, spawn_point, rigid_body_handle)) =
+            query.get_mut(entity)
+        {
+            lives.0 = lives.0.saturating_sub(1);
+            // there is nowhere to come back to once sudden death has walled in the spawn tile
+            let walled_in = grid
+                .to_tile(spawn_point.0)
+                .map_or(false, |(x, y)| grid.get(x, y) == Some(Tile::Wall));
+            if walled_in {
+                lives.0 = 0;
+            }
+            if let Some(Human(slot)) = human {
+                if let Some(slot_lives) = runstate.lives.get_mut(*slot) {
+                    *slot_lives = lives.0;
+                }
+            }
+            if lives.0 > 0 {
+                // back to the start, blinking until it can be hit again
+                if let Some(rigid_body) = rigid_body_set.get_mut(rigid_body_handle.handle()) {
+                    let Vec2 { x, y } = spawn_point.0;
+                    rigid_body.set_position(Isometry2::translation(x, y), true);
+                }
+                commands.remove_one::<Stop>(entity);
+                commands.insert(
+                    entity,
+                    (
+                        Invulnerable::new(RESPAWN_INVULNERABLE_TIME),
+                        DrawBlinkTimer(Timer::from_seconds(0.1, true)),
+                    ),
+                );
+            } else {
+                commands.despawn_recursive(entity);
+                game_events.send(GameEvents::ScatterItems(collected));
+                human_out |= human.is_some();
+            }
+        }
+    }
+    // a bot or one of several players just drops out of the round,
+    // versus rounds are decided by whoever is left instead
+    if human_out
+        && !map.rules.last_standing_wins
+        && query
+            .iter_mut()
+            .filter(|(_, human, ..)| human.is_some())
+            .all(|(_, _, lives, ..)| lives.0 == 0)
+    {
+        game_events.send(GameEvents::GameOver);
+    }
+}
@@ -1,10 +1,24 @@
 use crate::{
-    components::{Player, Portal, Stop},
+    assets::CreatureTextureAtlas,
+    components::{Human, Invulnerable, Portal, Stop, FIRE_LIFETIME},
+    creatures::{Boss, CreatureKind},
+    entities::spawn_creature,
     events::*,
+    grid::{Grid, DIRECTIONS},
     utils::vecs_xy_intersect,
 };
 use bevy::ecs::{Query, ResMut, SystemStage, With};
 use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng};
+
+pub const PORTAL_WAVE_COOLDOWN: f32 = 2.0;
+const MAX_PORTAL_WAVES: u32 = 3;
+const PORTAL_WAVE_SIZE: usize = 3;
+const PORTAL_WAVE_KIND: CreatureKind = CreatureKind::Stalker;
+
+// How many waves this level's portal has already let out.
+#[derive(Default)]
+pub struct PortalWaves(pub u32);
 
 pub trait PortalSystems {
     fn portal_systems(&mut self) -> &mut Self;
@@ -12,15 +26,21 @@ pub trait PortalSystems {
 impl PortalSystems for SystemStage {
     fn portal_systems(&mut self) -> &mut Self {
         self.add_system(portal_player_collision.system())
+            .add_system(portal_wave.system())
     }
 }
 
 fn portal_player_collision(
     commands: &mut Commands,
-    mut player_query: Query<(Entity, &mut Transform), With<Player>>,
+    mut player_query: Query<(Entity, &mut Transform), With<Human>>,
     mut portal_query: Query<(&mut Transform, &mut TextureAtlasSprite), With<Portal>>,
+    boss_query: Query<&Boss>,
     mut victory_events: ResMut<Events<GameEvents>>,
 ) {
+    // the portal stays shut until the boss is beaten
+    if boss_query.iter().next().is_some() {
+        return;
+    }
     for (entity, player_transform) in player_query.iter_mut() {
         let player_pos = &player_transform.translation.truncate();
         for (portal_transform, mut sprite_index) in portal_query.iter_mut() {
@@ -33,3 +53,45 @@ fn portal_player_collision(
         }
     }
 }
+
+fn portal_wave(
+    commands: &mut Commands,
+    grid: Res<Grid>,
+    game_events: Res<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    creature_texture_atlas: Res<CreatureTextureAtlas>,
+    mut portal_query: Query<(&Transform, &mut PortalWaves), With<Portal>>,
+) {
+    for event in events_reader.iter(&game_events) {
+        if let GameEvents::PortalHit(entity) = event {
+            if let Ok((transform, mut waves)) = portal_query.get_mut(*entity) {
+                if waves.0 >= MAX_PORTAL_WAVES {
+                    continue;
+                }
+                waves.0 += 1;
+                let portal = transform.translation.truncate();
+                let mut tiles = vec![portal];
+                if let Some((x, y)) = grid.to_tile(portal) {
+                    for &direction in DIRECTIONS.iter() {
+                        if let Some((nx, ny)) = grid.neighbour(x, y, direction) {
+                            if grid.is_walkable(nx, ny) {
+                                tiles.push(grid.to_position(nx, ny));
+                            }
+                        }
+                    }
+                }
+                tiles[1..].shuffle(&mut thread_rng());
+                for &position in tiles.iter().cycle().take(PORTAL_WAVE_SIZE) {
+                    // the blast that opened the wave is still burning
+                    spawn_creature(
+                        commands,
+                        position,
+                        creature_texture_atlas.0.clone(),
+                        PORTAL_WAVE_KIND,
+                    )
+                    .with(Invulnerable::new(FIRE_LIFETIME));
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,133 @@
 use std::ops::{Deref, DerefMut};
 
-use crate::utils::TILE_WIDTH;
+use bevy::ecs::{FromResources, Resources};
+
+use crate::{controls::Bindings, utils::TILE_WIDTH};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Rules {
+    // exposed buffs are destroyed by fire
+    pub burnable_buffs: bool,
+    // a dead player's upgrades are scattered across the arena
+    pub scatter_items: bool,
+    // no exit portal, the last bomber alive wins
+    pub last_standing_wins: bool,
+    // lives every bomber starts with on normal difficulty
+    pub lives: u32,
+}
+impl Rules {
+    pub fn easy() -> Self {
+        Self {
+            burnable_buffs: false,
+            scatter_items: false,
+            last_standing_wins: false,
+            lives: 3,
+        }
+    }
+    pub fn classic() -> Self {
+        Self {
+            burnable_buffs: true,
+            scatter_items: false,
+            last_standing_wins: false,
+            lives: 3,
+        }
+    }
+    pub fn versus() -> Self {
+        Self {
+            burnable_buffs: true,
+            // upgrades stay in play for the bombers still standing
+            scatter_items: true,
+            last_standing_wins: true,
+            lives: 1,
+        }
+    }
+}
+
+// How far off a lane, in pixels, a player still gets slid into it when turning, 0 turns it off.
+pub struct CornerAssist {
+    pub tolerance: f32,
+}
+// Set in tiles next to the key bindings in controls.cfg.
+impl FromResources for CornerAssist {
+    fn from_resources(resources: &Resources) -> Self {
+        let bindings = resources.get::<Bindings>().unwrap();
+        Self {
+            tolerance: TILE_WIDTH * bindings.corner_assist,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Normal
+    }
+}
+
+// How much tougher than their kind's stats the creatures of a level are.
+#[derive(Clone, Copy, Debug)]
+pub struct Scaling {
+    pub speed: f32,
+    pub chase_range: u32,
+    pub extra_creatures: usize,
+    pub flees: bool,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Hard,
+        }
+    }
+    pub fn previous(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Easy,
+            Difficulty::Normal => Difficulty::Easy,
+            Difficulty::Hard => Difficulty::Normal,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+    pub fn lives(&self, rules: &Rules) -> u32 {
+        match self {
+            Difficulty::Easy => rules.lives + 2,
+            Difficulty::Normal => rules.lives,
+            Difficulty::Hard => rules.lives.saturating_sub(2).max(1),
+        }
+    }
+    // Every level past the first counts as one step, the harder presets start a few steps in.
+    pub fn scaling(&self, level: u32) -> Scaling {
+        let step = level.saturating_sub(1)
+            + match self {
+                Difficulty::Easy => 0,
+                Difficulty::Normal => 1,
+                Difficulty::Hard => 3,
+            };
+        Scaling {
+            speed: (0.8 + 0.08 * step as f32).min(1.6),
+            chase_range: step.min(8),
+            extra_creatures: (step as usize / 2).min(6),
+            flees: step >= 4,
+        }
+    }
+}
 
 pub struct Map {
     pub value: Vec<Vec<i32>>,
     pub is_final: bool,
+    pub rules: Rules,
     level: u32,
 }
 pub const MAX_HEIGHT: f32 = 11.0 * TILE_WIDTH;
@@ -13,55 +136,63 @@ impl Map {
     pub fn first() -> Self {
         let room_map = vec![
             vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-            vec![1, 0, 2, 2, 0, 4, 7, 0, 2, 0, 0, 7, 1],
+            vec![1, 18, 2, 2, 0, 4, 7, 0, 2, 0, 0, 7, 1],
             vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 0, 9, 0, 1],
             vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 2, 0, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 6, 0, 0, 0, 5, 8, 1],
+            vec![1, 19, 0, 0, 0, 0, 6, 0, 0, 0, 5, 8, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 9, 1],
-            vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 2, 2, 0, 1],
+            vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 2, 10, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 0, 1],
-            vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+            vec![1, 3, 8, 0, 0, 0, 0, 20, 0, 0, 2, 0, 1],
             vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
         ];
         Self {
             value: room_map,
             is_final: false,
+            rules: Rules::easy(),
             level: 1,
         }
     }
     pub fn init(&mut self) {
         self.value = vec![
             vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-            vec![1, 0, 2, 2, 0, 4, 7, 0, 2, 0, 0, 7, 1],
+            vec![1, 18, 2, 2, 0, 4, 7, 0, 2, 0, 0, 7, 1],
             vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 0, 9, 0, 1],
             vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 2, 0, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 6, 0, 0, 0, 5, 8, 1],
+            vec![1, 19, 0, 0, 0, 0, 6, 0, 0, 0, 5, 8, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 9, 1],
-            vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 2, 2, 0, 1],
+            vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 2, 10, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 0, 1],
-            vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+            vec![1, 3, 8, 0, 0, 0, 0, 20, 0, 0, 2, 0, 1],
             vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
         ];
         self.is_final = false;
+        self.rules = Rules::easy();
         self.level = 1;
     }
     pub fn next(&mut self) {
+        if self.rules.last_standing_wins {
+            // another round in the same arena
+            self.versus();
+            return;
+        }
         match self.level {
             1 => {
                 self.level = 2;
+                self.rules = Rules::classic();
                 self.value = vec![
                     vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-                    vec![1, 0, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
+                    vec![1, 18, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
                     vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 7, 1, 0, 1],
                     vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 7, 1, 0, 1],
                     vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 0, 1],
-                    vec![1, 0, 0, 0, 0, 0, 6, 0, 0, 7, 5, 8, 1],
+                    vec![1, 19, 0, 0, 0, 0, 6, 0, 0, 7, 5, 8, 1],
                     vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 9, 1],
-                    vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 7, 2, 0, 1],
+                    vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 7, 10, 0, 1],
                     vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 4, 1, 1],
-                    vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+                    vec![1, 3, 8, 0, 0, 0, 0, 20, 0, 0, 2, 0, 1],
                     vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
                 ];
             }
@@ -69,31 +200,32 @@ impl Map {
                 self.level = 3;
                 self.value = vec![
                     vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-                    vec![1, 0, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
+                    vec![1, 18, 2, 2, 0, 4, 0, 0, 11, 0, 0, 7, 1],
                     vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 7, 1, 0, 1],
-                    vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 7, 1, 0, 1],
-                    vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 0, 1],
-                    vec![1, 0, 0, 0, 0, 0, 6, 0, 0, 7, 5, 8, 1],
+                    vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 13, 1, 0, 1],
+                    vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 17, 9, 0, 1],
+                    vec![1, 19, 0, 0, 0, 0, 6, 0, 0, 13, 5, 8, 1],
                     vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 9, 1],
-                    vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 7, 2, 0, 1],
+                    vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 7, 10, 0, 1],
                     vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 4, 1, 1],
-                    vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+                    vec![1, 3, 8, 0, 0, 0, 0, 20, 0, 0, 2, 0, 1],
                     vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
                 ];
             }
             3 => {
                 self.level = 4;
+                // boss arena
                 self.value = vec![
                     vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-                    vec![1, 0, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
-                    vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 7, 1, 0, 1],
-                    vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 7, 1, 0, 1],
-                    vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 0, 1],
-                    vec![1, 0, 0, 0, 0, 0, 6, 0, 0, 7, 5, 8, 1],
-                    vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 9, 9, 1],
-                    vec![1, 0, 0, 6, 0, 6, 0, 0, 0, 7, 2, 0, 1],
-                    vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 7, 4, 1, 1],
-                    vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+                    vec![1, 18, 0, 2, 0, 4, 0, 5, 0, 2, 0, 19, 1],
+                    vec![1, 0, 9, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1],
+                    vec![1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1],
+                    vec![1, 0, 0, 0, 9, 0, 15, 0, 0, 9, 0, 0, 1],
+                    vec![1, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10, 1],
+                    vec![1, 0, 0, 0, 9, 0, 0, 0, 0, 9, 0, 0, 1],
+                    vec![1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1],
+                    vec![1, 0, 9, 0, 0, 0, 0, 0, 0, 0, 9, 0, 1],
+                    vec![1, 3, 0, 2, 0, 4, 0, 2, 0, 12, 20, 8, 1],
                     vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
                 ];
                 self.is_final = true;
@@ -101,19 +233,41 @@ impl Map {
             _ => self.random(),
         }
     }
+    // An arena without creatures or portal, bots fill the spawn points nobody plays.
+    pub fn versus(&mut self) {
+        self.value = vec![
+            vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
+            vec![1, 19, 0, 2, 2, 2, 2, 2, 2, 2, 0, 18, 1],
+            vec![1, 0, 9, 2, 9, 2, 9, 2, 9, 2, 9, 0, 1],
+            vec![1, 2, 2, 2, 2, 4, 2, 5, 2, 2, 2, 2, 1],
+            vec![1, 2, 9, 2, 9, 2, 9, 2, 9, 2, 9, 2, 1],
+            vec![1, 2, 2, 6, 2, 2, 2, 2, 2, 6, 2, 2, 1],
+            vec![1, 2, 9, 2, 9, 2, 9, 2, 9, 2, 9, 2, 1],
+            vec![1, 2, 2, 2, 2, 5, 2, 4, 2, 2, 2, 2, 1],
+            vec![1, 0, 9, 2, 9, 2, 9, 2, 9, 2, 9, 0, 1],
+            vec![1, 3, 0, 2, 2, 2, 2, 2, 2, 2, 0, 20, 1],
+            vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
+        ];
+        self.is_final = false;
+        self.rules = Rules::versus();
+        self.level = 1;
+    }
+    pub fn level(&self) -> u32 {
+        self.level
+    }
     pub fn random(&mut self) {
         //TODO:Real random
         let room_map = vec![
             vec![1, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 1],
-            vec![1, 0, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
+            vec![1, 18, 2, 2, 0, 4, 0, 0, 2, 0, 0, 7, 1],
             vec![1, 0, 9, 0, 9, 4, 9, 0, 9, 0, 1, 0, 1],
-            vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 7, 1, 0, 1],
+            vec![1, 0, 0, 4, 0, 0, 0, 0, 0, 13, 1, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 0, 1],
-            vec![1, 0, 0, 0, 7, 0, 6, 0, 0, 0, 5, 8, 1],
+            vec![1, 19, 0, 0, 7, 0, 6, 0, 0, 0, 5, 8, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 9, 9, 1],
-            vec![1, 0, 0, 6, 0, 6, 0, 7, 0, 7, 2, 0, 1],
+            vec![1, 0, 0, 6, 0, 6, 0, 14, 0, 17, 10, 0, 1],
             vec![1, 0, 9, 0, 9, 0, 9, 0, 9, 0, 4, 1, 1],
-            vec![1, 3, 8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1],
+            vec![1, 3, 8, 0, 0, 0, 0, 20, 0, 0, 2, 0, 1],
             vec![9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9, 9],
         ];
         self.value = room_map;
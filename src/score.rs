@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{Human, Stop},
+    events::GameEvents,
+    state::RunState,
+};
+
+pub trait ScoreSystems {
+    fn score_systems(&mut self) -> &mut Self;
+}
+impl ScoreSystems for SystemStage {
+    fn score_systems(&mut self) -> &mut Self {
+        self.add_system(score_events.system())
+            .add_system(level_timer.system())
+    }
+}
+
+const BOX_POINTS: u32 = 10;
+const ITEM_POINTS: u32 = 50;
+// Every second under par is worth this many points when the level is cleared.
+const TIME_BONUS_POINTS: u32 = 10;
+const PAR_TIME: f32 = 120.0;
+// Chained kills double up to this many times.
+const MAX_CHAIN: u32 = 4;
+
+// The running total of a campaign, kept in `RunState` across levels.
+#[derive(Default)]
+pub struct Score {
+    pub total: u32,
+    pub level_time: f32,
+    level_cleared: bool,
+    // kills so far for each bomb of the level
+    chains: Vec<(Entity, u32)>,
+}
+
+impl Score {
+    pub fn reset(&mut self) {
+        *self = Score::default();
+    }
+    pub fn start_level(&mut self) {
+        self.level_time = 0.0;
+        self.level_cleared = false;
+        self.chains.clear();
+    }
+    // Every earlier kill by the same bomb doubles the points.
+    fn kill(&mut self, bomb: Entity, points: u32) {
+        let chain = match self
+            .chains
+            .iter_mut()
+            .find(|(chain_bomb, _)| *chain_bomb == bomb)
+        {
+            Some((_, kills)) => {
+                *kills += 1;
+                *kills
+            }
+            None => {
+                self.chains.push((bomb, 1));
+                1
+            }
+        };
+        self.total += points << (chain - 1).min(MAX_CHAIN);
+    }
+    fn clear_level(&mut self) {
+        if !self.level_cleared {
+            self.level_cleared = true;
+            let seconds_left = (PAR_TIME - self.level_time).max(0.0) as u32;
+            self.total += seconds_left * TIME_BONUS_POINTS;
+        }
+    }
+}
+
+// Only what the players at the keyboard do is worth points.
+fn score_events(
+    mut runstate: ResMut<RunState>,
+    game_events: Res<Events<GameEvents>>,
+    mut events_reader: Local<EventReader<GameEvents>>,
+    human_query: Query<&Human>,
+) {
+    let score = &mut runstate.score;
+    for event in events_reader.iter(&game_events) {
+        match event {
+            GameEvents::BoxDestroyed(blast) if human_query.get(blast.owner).is_ok() => {
+                score.total += BOX_POINTS;
+            }
+            GameEvents::CreatureKilled(kind, blast) if human_query.get(blast.owner).is_ok() => {
+                score.kill(blast.bomb, kind.stats().score);
+            }
+            GameEvents::ItemCollected(player) if human_query.get(*player).is_ok() => {
+                score.total += ITEM_POINTS;
+            }
+            GameEvents::Victory => score.clear_level(),
+            _ => {}
+        }
+    }
+}
+
+fn level_timer(
+    time: Res<Time>,
+    mut runstate: ResMut<RunState>,
+    query: Query<Entity, (With<Human>, Without<Stop>)>,
+) {
+    // paused and finished levels leave the players stopped
+    if query.iter().next().is_some() {
+        runstate.score.level_time += time.delta_seconds();
+    }
+}
@@ -1,26 +1,56 @@
 use crate::{
     assets::*,
-    components::{AnimateIndexs, Player},
+    characters::Characters,
+    components::{GameMode, Seat},
+    creatures::CreatureKind,
     entities::{
-        create_bomb_number_buff_box, create_creature, create_green_way, create_last_wall,
-        create_normal_box, create_normal_wall, create_player, create_portal_box,
-        create_power_buff_box, create_speed_buff_box,
+        create_bomb_number_buff_box, create_bomb_pass_buff_box, create_bot, create_creature,
+        create_green_way, create_last_wall, create_normal_box, create_normal_wall, create_player,
+        create_portal_box, create_power_buff_box, create_shield_buff_box, create_speed_buff_box,
+        create_wall_pass_buff_box, spawn_creature,
     },
-    resources::Map,
+    resources::{Difficulty, Map},
     state::RunState,
     utils::index_to_position,
 };
 use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng};
+
+// Extra creatures keep at least this many tiles away from the players' starts.
+const EXTRA_CREATURE_DISTANCE: usize = 5;
+
+// Tile 3 is where the first player starts, 18 to 20 are the others' spawn points.
+fn spawn_slot(cell: i32) -> Option<usize> {
+    match cell {
+        3 => Some(0),
+        18..=20 => Some(cell as usize - 17),
+        _ => None,
+    }
+}
 
 pub fn setup_map(
     commands: &mut Commands,
     room_map: Res<Map>,
     player_texture_atlas: Res<PlayerTextureAtlas>,
     floor_or_wall_texture_atlas: Res<FloorOrWallTextureAtlas>,
-    player_animate_indexs: Res<AnimateIndexs<Player>>,
-    //creature_texture_atlas: Res<CreatureTextureAtlas>,
+    creature_texture_atlas: Res<CreatureTextureAtlas>,
+    difficulty: Res<Difficulty>,
+    game_mode: Res<GameMode>,
+    characters: Res<Characters>,
     mut runstate: ResMut<RunState>,
 ) {
+    runstate.score.start_level();
+    if room_map.rules.last_standing_wins {
+        runstate.battle.start_round();
+    }
+    runstate.players.clear();
+    // a new run or a new versus round hands out fresh lives
+    let lives = difficulty.lives(&room_map.rules);
+    if runstate.lives.is_empty() || room_map.rules.last_standing_wins {
+        runstate.lives = vec![lives; game_mode.player_count()];
+    }
+    let mut free_tiles = Vec::new();
+    let mut starts = Vec::new();
     for (row_index, row) in room_map.iter().enumerate() {
         for (col_index, cell) in row.iter().enumerate() {
             // Using match here makes it easier to extend the map
@@ -35,16 +65,43 @@ pub fn setup_map(
                     create_normal_box(commands, translation, floor_or_wall_texture_atlas.0.clone())
                 }
                 // When setting each level, the player’s position should be set flexibly
-                3 => {
-                    // player
-                    let player = create_player(
-                        commands,
-                        translation,
-                        floor_or_wall_texture_atlas.0.clone(),
-                        player_texture_atlas.0.clone(),
-                        player_animate_indexs.up[0],
-                    );
-                    runstate.player = player;
+                3 | 18 | 19 | 20 => {
+                    let slot = spawn_slot(*cell).unwrap_or(0);
+                    let human = slot < game_mode.player_count();
+                    let player_lives = runstate.lives.get(slot).copied().unwrap_or(lives);
+                    // a player out of lives sits out the rest of the run
+                    if human && player_lives > 0 {
+                        if let Some(player) = create_player(
+                            commands,
+                            translation,
+                            floor_or_wall_texture_atlas.0.clone(),
+                            player_texture_atlas.0.clone(),
+                            slot,
+                            characters.get(slot),
+                            player_lives,
+                        ) {
+                            runstate.players.push(player);
+                        }
+                    } else if !human && room_map.rules.last_standing_wins {
+                        // a bot takes the seat nobody is playing, and the character left over
+                        if let Some(bot) = create_bot(
+                            commands,
+                            translation,
+                            floor_or_wall_texture_atlas.0.clone(),
+                            player_texture_atlas.0.clone(),
+                            characters.get(slot),
+                            *difficulty,
+                            lives,
+                        ) {
+                            commands.insert_one(bot, Seat(slot));
+                        }
+                    } else {
+                        create_green_way(
+                            commands,
+                            translation,
+                            floor_or_wall_texture_atlas.0.clone(),
+                        );
+                    }
                 }
                 4 => {
                     create_bomb_number_buff_box(
@@ -68,13 +125,16 @@ pub fn setup_map(
                         floor_or_wall_texture_atlas.0.clone(),
                     );
                 }
-                7 => {
-                    create_creature(
-                        commands,
-                        translation,
-                        floor_or_wall_texture_atlas.0.clone(),
-                        player_texture_atlas.0.clone(), //TODO:creature_texture_atlas
-                    );
+                7 | 13 | 14 | 15 | 17 => {
+                    if let Some(kind) = CreatureKind::from_tile(*cell) {
+                        create_creature(
+                            commands,
+                            translation,
+                            floor_or_wall_texture_atlas.0.clone(),
+                            creature_texture_atlas.0.clone(),
+                            kind,
+                        );
+                    }
                 }
                 8 => {
                     create_portal_box(commands, translation, floor_or_wall_texture_atlas.0.clone());
@@ -82,10 +142,66 @@ pub fn setup_map(
                 9 => {
                     create_last_wall(commands, translation, floor_or_wall_texture_atlas.0.clone());
                 }
+                10 => {
+                    create_shield_buff_box(
+                        commands,
+                        translation,
+                        floor_or_wall_texture_atlas.0.clone(),
+                    );
+                }
+                11 => {
+                    create_wall_pass_buff_box(
+                        commands,
+                        translation,
+                        floor_or_wall_texture_atlas.0.clone(),
+                    );
+                }
+                12 => {
+                    create_bomb_pass_buff_box(
+                        commands,
+                        translation,
+                        floor_or_wall_texture_atlas.0.clone(),
+                    );
+                }
                 _ => {
                     create_green_way(commands, translation, floor_or_wall_texture_atlas.0.clone());
                 }
             }
+            match *cell {
+                0 => free_tiles.push((col_index, row_index)),
+                3 | 18 | 19 | 20 => starts.push((col_index, row_index)),
+                _ => {}
+            }
         }
     }
+    // the harder the level, the more creatures on top of the map's own
+    let distance = |&(x, y): &(usize, usize), &(sx, sy): &(usize, usize)| {
+        (x as isize - sx as isize).abs() as usize + (y as isize - sy as isize).abs() as usize
+    };
+    free_tiles.retain(|tile| {
+        starts
+            .iter()
+            .all(|start| distance(tile, start) >= EXTRA_CREATURE_DISTANCE)
+    });
+    free_tiles.shuffle(&mut thread_rng());
+    let extra = if room_map.rules.last_standing_wins {
+        0
+    } else {
+        difficulty.scaling(room_map.level()).extra_creatures
+    };
+    for (index, &(x, y)) in free_tiles.iter().take(extra).enumerate() {
+        let translation = index_to_position(x, y, room_map.len());
+        // every third one is a stalker
+        let kind = if index % 3 == 2 {
+            CreatureKind::Stalker
+        } else {
+            CreatureKind::Walker
+        };
+        spawn_creature(
+            commands,
+            translation,
+            creature_texture_atlas.0.clone(),
+            kind,
+        );
+    }
 }
@@ -2,20 +2,33 @@ use bevy::prelude::*;
 use bevy_rapier2d::physics::RapierConfiguration;
 
 use crate::{
+    battle::{Battle, BattleSystems, RoundOver},
     bomb::BombSystems,
+    bot::BotSystems,
     buff::BuffSystems,
+    characters::{character_select, enter_character_select},
     components::{Animation, Bomb, Fire, InGame, Player, Stop},
+    controls::controls_menu,
     creatures::{Creature, CreatureSystems},
+    grid::GridSystems,
     physics::PhysicsSystems,
-    player::PlayerSystems,
+    player::{Death, PlayerSystems},
     portal::PortalSystems,
+    score::{Score, ScoreSystems},
     setup_map::setup_map,
-    ui::{button_system, game_victory, gameover_menu, pause_menu, start_menu, WillDestroy},
+    ui::{
+        button_system, difficulty_ui, game_victory, gameover_menu, pause_menu, players_ui,
+        spawn_character_select, spawn_controls_menu, spawn_lives_ui, spawn_score_ui, start_menu,
+        update_character_select, update_controls_menu, update_lives_ui, update_score_ui,
+        WillDestroy,
+    },
 };
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum AppState {
     StartMenu,
+    Controls,
+    CharacterSelect,
     Game,
     Temporary,
 }
@@ -35,18 +48,39 @@ impl Plugin for AppStatePluge {
                     // start menu
                     .on_state_enter(AppState::StartMenu, start_menu.system())
                     .on_state_update(AppState::StartMenu, button_system.system())
+                    .on_state_update(AppState::StartMenu, difficulty_ui.system())
+                    .on_state_update(AppState::StartMenu, players_ui.system())
                     .on_state_exit(AppState::StartMenu, exit_ui_despawn.system())
+                    // controls menu
+                    .on_state_enter(AppState::Controls, spawn_controls_menu.system())
+                    .on_state_update(AppState::Controls, controls_menu.system())
+                    .on_state_update(AppState::Controls, update_controls_menu.system())
+                    .on_state_exit(AppState::Controls, exit_ui_despawn.system())
+                    // character select
+                    .on_state_enter(AppState::CharacterSelect, enter_character_select.system())
+                    .on_state_enter(AppState::CharacterSelect, spawn_character_select.system())
+                    .on_state_update(AppState::CharacterSelect, character_select.system())
+                    .on_state_update(AppState::CharacterSelect, update_character_select.system())
+                    .on_state_exit(AppState::CharacterSelect, exit_ui_despawn.system())
                     // in game
                     .on_state_enter(AppState::Game, setup_map.system())
+                    .on_state_enter(AppState::Game, spawn_score_ui.system())
+                    .on_state_enter(AppState::Game, spawn_lives_ui.system())
                     //.on_state_enter(AppState::Game, spawn_game_ui.system())
                     .update_stage(AppState::Game, |stage: &mut SystemStage| {
                         stage
+                            .grid_systems()
                             .physics_systems()
                             .player_systems()
+                            .bot_systems()
+                            .battle_systems()
                             .bomb_systems()
                             .buff_systems()
                             .creature_systems()
                             .portal_systems()
+                            .score_systems()
+                            .add_system(update_score_ui.system())
+                            .add_system(update_lives_ui.system())
                     })
                     .on_state_exit(AppState::Game, exit_game_despawn.system())
                     .on_state_enter(AppState::Temporary, jump_game.system())
@@ -116,6 +150,7 @@ fn pause_enter(
     mut animation_query: Query<&mut Animation>,
     mut bomb_query: Query<&mut Bomb>,
     mut fire_query: Query<&mut Fire>,
+    mut death_query: Query<&mut Death>,
 ) {
     for entity in player_query.iter() {
         commands.insert_one(entity, Stop);
@@ -132,13 +167,19 @@ fn pause_enter(
     for mut fire in fire_query.iter_mut().filter(|a| !a.0.paused()) {
         fire.0.pause();
     }
+    for mut death in death_query.iter_mut().filter(|a| !a.timer.paused()) {
+        death.timer.pause();
+        death.spin.pause();
+    }
 }
 fn pause_exit(
     commands: &mut Commands,
-    query: Query<Entity, With<Stop>>,
+    // a dying player stays frozen until the death has played out, a round winner until the results
+    query: Query<Entity, (With<Stop>, Without<Death>, Without<RoundOver>)>,
     mut animation_query: Query<&mut Animation>,
     mut bomb_query: Query<&mut Bomb>,
     mut fire_query: Query<&mut Fire>,
+    mut death_query: Query<&mut Death>,
 ) {
     for entity in query.iter() {
         commands.remove_one::<Stop>(entity);
@@ -152,19 +193,31 @@ fn pause_exit(
     for mut fire in fire_query.iter_mut().filter(|a| a.0.paused()) {
         fire.0.unpause();
     }
+    for mut death in death_query.iter_mut().filter(|a| a.timer.paused()) {
+        death.timer.unpause();
+        death.spin.unpause();
+    }
 }
 pub struct RunState {
-    pub player: Option<Entity>,
+    // the players at the keyboard, by slot
+    pub players: Vec<Entity>,
+    // lives left by slot, carried from one level to the next
+    pub lives: Vec<u32>,
     pub font_handle: Handle<Font>,
     pub level: Option<i32>,
+    pub score: Score,
+    pub battle: Battle,
 }
 
 impl RunState {
     pub fn new(asset_server: &AssetServer) -> Self {
         Self {
-            player: None,
+            players: Vec::new(),
+            lives: Vec::new(),
             font_handle: asset_server.load("fonts/FiraMono-Medium.ttf"),
             level: None,
+            score: Score::default(),
+            battle: Battle::default(),
         }
     }
 }
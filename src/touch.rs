@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+
+use crate::{controls::Action, ui::spawn_touch_controls};
+
+// The on-screen pad, it comes up with the first touch and plays the first player.
+#[derive(Default)]
+pub struct TouchControls {
+    pub enabled: bool,
+    pub held: Vec<Action>,
+}
+
+// An on-screen button, its actions are held while a finger is on it.
+pub struct TouchButton(pub &'static [Action]);
+
+pub fn touch_actions(
+    commands: &mut Commands,
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+    mut touch_controls: ResMut<TouchControls>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    button_query: Query<(&Node, &GlobalTransform, &TouchButton)>,
+) {
+    if !touch_controls.enabled {
+        if touches.iter().next().is_none() {
+            return;
+        }
+        touch_controls.enabled = true;
+        spawn_touch_controls(commands, &mut materials);
+    }
+    let height = match windows.get_primary() {
+        Some(window) => window.height(),
+        None => return,
+    };
+    let mut held = Vec::new();
+    for touch in touches.iter() {
+        // touches count from the top of the window, the UI from the bottom
+        let position = Vec2::new(touch.position().x, height - touch.position().y);
+        for (node, transform, button) in button_query.iter() {
+            let offset = position - transform.translation.truncate();
+            if offset.x.abs() <= node.size.x / 2.0 && offset.y.abs() <= node.size.y / 2.0 {
+                for action in button.0.iter() {
+                    if !held.contains(action) {
+                        held.push(*action);
+                    }
+                }
+            }
+        }
+    }
+    touch_controls.held = held;
+}
+
+// Bevy 0.4's web backend hands out no touch events, so the canvas's own are fed into `Touches`.
+#[cfg(target_arch = "wasm32")]
+pub mod web {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::{
+        input::touch::{TouchInput, TouchPhase},
+        prelude::*,
+    };
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    const CANVAS_ID: &str = "bevy-canvas";
+
+    // Touches the browser reported since the last frame.
+    #[derive(Default)]
+    pub struct WebTouches(Arc<Mutex<Vec<TouchInput>>>);
+
+    pub fn listen_web_touches(web_touches: Res<WebTouches>) {
+        let canvas = match web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id(CANVAS_ID))
+        {
+            Some(canvas) => canvas,
+            None => {
+                warn!("no #{} to listen for touches on", CANVAS_ID);
+                return;
+            }
+        };
+        let phases = [
+            ("touchstart", TouchPhase::Started),
+            ("touchmove", TouchPhase::Moved),
+            ("touchend", TouchPhase::Ended),
+            ("touchcancel", TouchPhase::Cancelled),
+        ];
+        for &(name, phase) in phases.iter() {
+            let queue = web_touches.0.clone();
+            let target = canvas.clone();
+            let listener = Closure::wrap(Box::new(move |event: web_sys::TouchEvent| {
+                // keeps the page from scrolling and zooming under the pad
+                event.prevent_default();
+                let rect = target.get_bounding_client_rect();
+                let touches = event.changed_touches();
+                let mut queue = queue.lock().unwrap();
+                for touch in (0..touches.length()).filter_map(|i| touches.get(i)) {
+                    queue.push(TouchInput {
+                        phase,
+                        position: Vec2::new(
+                            (touch.client_x() as f64 - rect.left()) as f32,
+                            (touch.client_y() as f64 - rect.top()) as f32,
+                        ),
+                        force: None,
+                        id: touch.identifier() as u64,
+                    });
+                }
+            }) as Box<dyn FnMut(web_sys::TouchEvent)>);
+            if canvas
+                .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                .is_err()
+            {
+                warn!("listening for {} failed", name);
+            }
+            // the listeners live as long as the page
+            listener.forget();
+        }
+    }
+
+    pub fn forward_web_touches(
+        web_touches: Res<WebTouches>,
+        mut touch_events: ResMut<Events<TouchInput>>,
+    ) {
+        for touch in web_touches.0.lock().unwrap().drain(..) {
+            touch_events.send(touch);
+        }
+    }
+}
@@ -2,21 +2,37 @@ use bevy::prelude::*;
 use bevy_rapier2d::physics::RapierConfiguration;
 
 use crate::{
-    components::InGame,
+    assets::{LifeMaterial, PlayerTextureAtlas},
+    battle::{Outcome, ROUNDS_TO_WIN},
+    characters::{CharacterSelect, Characters},
+    components::{AnimateIndexs, GameMode, HitPoints, InGame, Player, MAX_PLAYERS},
     constants::START_SPEED,
-    resources::Map,
-    state::{AppState, GameState, RunState},
+    controls::{key_name, Action, Bindings, ControlsMenu, ACTIONS},
+    creatures::Boss,
+    resources::{Difficulty, Map, MAX_WIDTH},
+    state::{AppState, RunState},
+    touch::TouchButton,
+    utils::{SCALE, TILE_WIDTH},
 };
 
 pub struct DrawBlinkTimer(pub Timer);
 pub struct WillDestroy;
 
+pub struct DifficultyUi;
+pub struct PlayersUi;
+
 pub fn start_menu(
     commands: &mut Commands,
-    runstate: Res<RunState>,
+    mut runstate: ResMut<RunState>,
+    difficulty: Res<Difficulty>,
+    game_mode: Res<GameMode>,
     button_materials: Res<ButtonMaterials>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
+    // every campaign starts from zero
+    runstate.score.reset();
+    runstate.lives.clear();
+    runstate.battle.reset();
     commands
         .spawn(NodeBundle {
             style: Style {
@@ -76,9 +92,220 @@ pub fn start_menu(
                             ..Default::default()
                         })
                         .with(DrawBlinkTimer(Timer::from_seconds(0.5, true)));
+                })
+                .spawn(TextBundle {
+                    text: Text {
+                        value: difficulty_text(*difficulty),
+                        font: runstate.font_handle.clone(),
+                        style: TextStyle {
+                            font_size: 40.0,
+                            color: Color::rgb_u8(0x00, 0xAA, 0xAA),
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .with(DifficultyUi)
+                .spawn(TextBundle {
+                    text: Text {
+                        value: players_text(*game_mode),
+                        font: runstate.font_handle.clone(),
+                        style: TextStyle {
+                            font_size: 40.0,
+                            color: Color::rgb_u8(0x00, 0xAA, 0xAA),
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
+                })
+                .with(PlayersUi)
+                .spawn(TextBundle {
+                    text: Text {
+                        value: "V: versus bots  C: controls".to_string(),
+                        font: runstate.font_handle.clone(),
+                        style: TextStyle {
+                            font_size: 30.0,
+                            color: Color::rgb_u8(0x00, 0x44, 0x44),
+                            ..Default::default()
+                        },
+                    },
+                    ..Default::default()
                 });
         });
 }
+fn difficulty_text(difficulty: Difficulty) -> String {
+    format!("< {} >", difficulty.name())
+}
+pub fn difficulty_ui(
+    difficulty: ChangedRes<Difficulty>,
+    mut query: Query<&mut Text, With<DifficultyUi>>,
+) {
+    for mut text in query.iter_mut() {
+        text.value = difficulty_text(*difficulty);
+    }
+}
+fn players_text(game_mode: GameMode) -> String {
+    match game_mode.player_count() {
+        1 => "^ 1 player v".to_string(),
+        count => format!("^ {} players v", count),
+    }
+}
+pub fn players_ui(game_mode: ChangedRes<GameMode>, mut query: Query<&mut Text, With<PlayersUi>>) {
+    for mut text in query.iter_mut() {
+        text.value = players_text(*game_mode);
+    }
+}
+pub struct ControlsPlayerUi;
+// The text of one action's binding, by index into `ACTIONS`.
+pub struct ControlsRowUi(pub usize);
+
+pub fn spawn_controls_menu(
+    commands: &mut Commands,
+    runstate: Res<RunState>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text = |value: &str, font_size: f32| TextBundle {
+        text: Text {
+            value: value.to_string(),
+            font: runstate.font_handle.clone(),
+            style: TextStyle {
+                font_size,
+                color: Color::rgb_u8(0x00, 0xAA, 0xAA),
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(WillDestroy)
+        .with_children(|parent| {
+            parent
+                .spawn(text("Controls", 60.0))
+                .spawn(text("", 40.0))
+                .with(ControlsPlayerUi);
+            for row in 0..ACTIONS.len() {
+                parent.spawn(text("", 30.0)).with(ControlsRowUi(row));
+            }
+            parent.spawn(text("Confirm: rebind  Back: done", 25.0));
+        });
+}
+pub fn update_controls_menu(
+    bindings: Res<Bindings>,
+    menu: Res<ControlsMenu>,
+    mut player_query: Query<&mut Text, With<ControlsPlayerUi>>,
+    mut row_query: Query<(&mut Text, &ControlsRowUi)>,
+) {
+    for mut text in player_query.iter_mut() {
+        text.value = format!("< Player {} >", menu.slot + 1);
+    }
+    for (mut text, row) in row_query.iter_mut() {
+        let action = ACTIONS[row.0];
+        let key = if menu.row == row.0 && menu.listening {
+            "press a key"
+        } else {
+            bindings
+                .key(menu.slot, action)
+                .and_then(key_name)
+                .unwrap_or("-")
+        };
+        let cursor = if menu.row == row.0 { ">" } else { " " };
+        text.value = format!("{} {}: {}", cursor, action.name(), key);
+    }
+}
+pub struct CharacterRowUi(pub usize);
+// A player's pick, standing in a row at the bottom of the screen.
+pub struct CharacterPreview(pub usize);
+
+pub fn spawn_character_select(
+    commands: &mut Commands,
+    runstate: Res<RunState>,
+    game_mode: Res<GameMode>,
+    characters: Res<Characters>,
+    player_texture_atlas: Res<PlayerTextureAtlas>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text = |value: &str, font_size: f32| TextBundle {
+        text: Text {
+            value: value.to_string(),
+            font: runstate.font_handle.clone(),
+            style: TextStyle {
+                font_size,
+                color: Color::rgb_u8(0x00, 0xAA, 0xAA),
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    };
+    let count = game_mode.player_count();
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(WillDestroy)
+        .with_children(|parent| {
+            parent.spawn(text("Choose your bomber", 60.0));
+            for slot in 0..count {
+                parent.spawn(text("", 40.0)).with(CharacterRowUi(slot));
+            }
+            parent.spawn(text("Bomb: ready  Detonate: change  Back: menu", 25.0));
+        });
+    let center_x = (MAX_WIDTH - TILE_WIDTH) / 2.0;
+    for slot in 0..count {
+        let offset = slot as f32 - (count - 1) as f32 / 2.0;
+        commands
+            .spawn(SpriteSheetBundle {
+                texture_atlas: player_texture_atlas.0.clone(),
+                sprite: TextureAtlasSprite::new(
+                    AnimateIndexs::<Player>::for_character(characters.get(slot)).down[0],
+                ),
+                transform: Transform {
+                    translation: Vec3::new(center_x + offset * 2.0 * TILE_WIDTH, TILE_WIDTH, 0.0),
+                    scale: Vec3::splat(SCALE),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .with(CharacterPreview(slot))
+            .with(WillDestroy);
+    }
+}
+pub fn update_character_select(
+    characters: Res<Characters>,
+    select: Res<CharacterSelect>,
+    mut row_query: Query<(&mut Text, &CharacterRowUi)>,
+    mut preview_query: Query<(&mut TextureAtlasSprite, &CharacterPreview)>,
+) {
+    for (mut text, row) in row_query.iter_mut() {
+        let character = characters.get(row.0) + 1;
+        text.value = if select.ready.get(row.0).copied().unwrap_or(false) {
+            format!("P{}  Bomber {}  ready", row.0 + 1, character)
+        } else {
+            format!("P{} < Bomber {} >", row.0 + 1, character)
+        };
+    }
+    for (mut sprite, preview) in preview_query.iter_mut() {
+        sprite.index = AnimateIndexs::<Player>::for_character(characters.get(preview.0)).down[0];
+    }
+}
 pub struct ButtonMaterials {
     normal: Handle<ColorMaterial>,
     hovered: Handle<ColorMaterial>,
@@ -99,7 +326,6 @@ impl FromResources for ButtonMaterials {
 pub fn button_system(
     button_materials: Res<ButtonMaterials>,
     mut app_state: ResMut<State<AppState>>,
-    mut game_state: ResMut<State<GameState>>,
     mut interaction_query: Query<
         (&Interaction, &mut Handle<ColorMaterial>),
         (Mutated<Interaction>, With<Button>),
@@ -109,8 +335,7 @@ pub fn button_system(
         match *interaction {
             Interaction::Clicked => {
                 *material = button_materials.pressed.clone();
-                app_state.set_next(AppState::Game).unwrap();
-                game_state.set_next(GameState::Game).unwrap();
+                app_state.set_next(AppState::CharacterSelect).unwrap();
             }
             Interaction::Hovered => {
                 *material = button_materials.hovered.clone();
@@ -141,7 +366,7 @@ pub fn spawn_game_ui(
     mut materials: ResMut<Assets<ColorMaterial>>,
     runstate: Res<RunState>,
 ) {
-    if let Some(_player) = runstate.player {
+    if !runstate.players.is_empty() {
         commands
             // speed value
             .spawn(NodeBundle {
@@ -337,6 +562,8 @@ pub fn gameover_menu(
                     },
                     ..Default::default()
                 })
+                .spawn(score_text(&runstate, 50.0, Color::rgb_u8(0xAA, 0x22, 0x22)))
+                .with(ScoreUi)
                 .spawn(TextBundle {
                     style: Style {
                         ..Default::default()
@@ -359,8 +586,13 @@ pub fn game_victory(
     commands: &mut Commands,
     runstate: ResMut<RunState>,
     map: Res<Map>,
+    game_mode: Res<GameMode>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
+    if map.rules.last_standing_wins {
+        round_results(commands, &runstate, *game_mode, &mut materials);
+        return;
+    }
     commands
         .spawn(NodeBundle {
             style: Style {
@@ -392,6 +624,8 @@ pub fn game_victory(
                     },
                     ..Default::default()
                 })
+                .spawn(score_text(&runstate, 50.0, Color::rgb_u8(0xAA, 0x22, 0x22)))
+                .with(ScoreUi)
                 .spawn(TextBundle {
                     style: Style {
                         ..Default::default()
@@ -414,3 +648,314 @@ pub fn game_victory(
                 .with(DrawBlinkTimer(Timer::from_seconds(0.5, true)));
         });
 }
+
+pub struct BossHealthBar(pub Entity);
+
+pub fn spawn_boss_health_bar(
+    commands: &mut Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<Entity, Added<Boss>>,
+) {
+    for boss in query.iter() {
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Percent(25.0),
+                        top: Val::Px(10.0),
+                        ..Default::default()
+                    },
+                    size: Size::new(Val::Percent(50.0), Val::Px(16.0)),
+                    ..Default::default()
+                },
+                material: materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
+                ..Default::default()
+            })
+            .with(InGame)
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                            ..Default::default()
+                        },
+                        material: materials.add(Color::rgb(0.8, 0.1, 0.1).into()),
+                        ..Default::default()
+                    })
+                    .with(BossHealthBar(boss));
+            });
+    }
+}
+
+pub fn update_boss_health_bar(
+    commands: &mut Commands,
+    mut bar_query: Query<(&BossHealthBar, &Parent, &mut Style)>,
+    boss_query: Query<&HitPoints, With<Boss>>,
+) {
+    for (bar, parent, mut style) in bar_query.iter_mut() {
+        match boss_query.get(bar.0) {
+            Ok(hit_points) => {
+                let percent = 100.0 * hit_points.current.max(0) as f32 / hit_points.max as f32;
+                style.size.width = Val::Percent(percent);
+            }
+            Err(_) => commands.despawn_recursive(parent.0),
+        }
+    }
+}
+
+pub struct ScoreUi;
+
+// Seats nobody plays are taken by bots.
+fn seat_name(seat: usize, game_mode: GameMode) -> String {
+    if seat < game_mode.player_count() {
+        format!("P{}", seat + 1)
+    } else {
+        format!("Bot{}", seat + 1)
+    }
+}
+// The results between versus rounds, the match is over once someone has enough wins.
+fn round_results(
+    commands: &mut Commands,
+    runstate: &RunState,
+    game_mode: GameMode,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    let battle = &runstate.battle;
+    let title = match battle.outcome {
+        Some(Outcome::Won(seat)) => format!("{} wins", seat_name(seat, game_mode)),
+        _ => "Draw".to_string(),
+    };
+    let tally = (0..MAX_PLAYERS)
+        .map(|seat| format!("{} {}", seat_name(seat, game_mode), battle.wins(seat)))
+        .collect::<Vec<_>>()
+        .join("  ");
+    let next = match battle.champion() {
+        Some(seat) => format!("{} takes the match!", seat_name(seat, game_mode)),
+        None => format!("next round, first to {}", ROUNDS_TO_WIN),
+    };
+    let text = |value: String, font_size: f32, color: Color| TextBundle {
+        text: Text {
+            value,
+            font: runstate.font_handle.clone(),
+            style: TextStyle {
+                font_size,
+                color,
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(WillDestroy)
+        .with(InGame)
+        .with_children(|parent| {
+            parent
+                .spawn(text(
+                    format!("Round {}", battle.round),
+                    40.0,
+                    Color::rgb_u8(0x88, 0x22, 0x22),
+                ))
+                .spawn(text(title, 100.0, Color::rgb_u8(0xAA, 0x22, 0x22)))
+                .spawn(text(tally, 40.0, Color::rgb_u8(0xAA, 0x22, 0x22)))
+                .spawn(text(next, 50.0, Color::rgb_u8(0x88, 0x22, 0x22)))
+                .with(DrawBlinkTimer(Timer::from_seconds(0.5, true)));
+        });
+}
+fn score_text(runstate: &RunState, font_size: f32, color: Color) -> TextBundle {
+    TextBundle {
+        text: Text {
+            value: format!("Score: {}", runstate.score.total),
+            font: runstate.font_handle.clone(),
+            style: TextStyle {
+                font_size,
+                color,
+                ..Default::default()
+            },
+        },
+        ..Default::default()
+    }
+}
+
+pub fn spawn_score_ui(
+    commands: &mut Commands,
+    runstate: Res<RunState>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(InGame)
+        .with_children(|parent| {
+            parent
+                .spawn(score_text(&runstate, 40.0, Color::rgb_u8(0x00, 0xAA, 0xAA)))
+                .with(ScoreUi);
+        });
+}
+
+pub fn update_score_ui(runstate: Res<RunState>, mut query: Query<&mut Text, With<ScoreUi>>) {
+    for mut text in query.iter_mut() {
+        text.value = format!("Score: {}", runstate.score.total);
+    }
+}
+
+// One life square of the HUD, shown while the player in `slot` has more than `index` lives.
+pub struct LifeUi {
+    pub slot: usize,
+    pub index: u32,
+}
+
+pub fn spawn_lives_ui(
+    commands: &mut Commands,
+    runstate: Res<RunState>,
+    life_material: Res<LifeMaterial>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with(InGame)
+        .with_children(|parent| {
+            // a row of squares for every player
+            for (slot, &lives) in runstate.lives.iter().enumerate() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            ..Default::default()
+                        },
+                        material: materials.add(Color::NONE.into()),
+                        ..Default::default()
+                    })
+                    .with_children(|row| {
+                        for index in 0..lives {
+                            row.spawn(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(16.0), Val::Px(16.0)),
+                                    margin: Rect {
+                                        left: Val::Px(3.0),
+                                        right: Val::Px(3.0),
+                                        top: Val::Px(3.0),
+                                        bottom: Val::Px(3.0),
+                                    },
+                                    ..Default::default()
+                                },
+                                material: life_material.0.clone(),
+                                ..Default::default()
+                            })
+                            .with(LifeUi { slot, index });
+                        }
+                    });
+            }
+        });
+}
+
+pub fn update_lives_ui(runstate: Res<RunState>, mut query: Query<(&LifeUi, &mut Visible)>) {
+    for (life, mut visible) in query.iter_mut() {
+        visible.is_visible = runstate
+            .lives
+            .get(life.slot)
+            .map_or(false, |&lives| life.index < lives);
+    }
+}
+
+// A d-pad in the bottom left corner and a bomb button in the bottom right, over every screen.
+pub fn spawn_touch_controls(commands: &mut Commands, materials: &mut Assets<ColorMaterial>) {
+    let material = materials.add(Color::rgba(1.0, 1.0, 1.0, 0.2).into());
+    let buttons: [(&'static [Action], Rect<Val>, f32); 5] = [
+        (
+            &[Action::MoveUp],
+            Rect {
+                left: Val::Px(90.0),
+                bottom: Val::Px(170.0),
+                ..Default::default()
+            },
+            70.0,
+        ),
+        (
+            &[Action::MoveDown],
+            Rect {
+                left: Val::Px(90.0),
+                bottom: Val::Px(10.0),
+                ..Default::default()
+            },
+            70.0,
+        ),
+        (
+            &[Action::MoveLeft],
+            Rect {
+                left: Val::Px(10.0),
+                bottom: Val::Px(90.0),
+                ..Default::default()
+            },
+            70.0,
+        ),
+        (
+            &[Action::MoveRight],
+            Rect {
+                left: Val::Px(170.0),
+                bottom: Val::Px(90.0),
+                ..Default::default()
+            },
+            70.0,
+        ),
+        // also confirms in the menus
+        (
+            &[Action::PlaceBomb, Action::Confirm],
+            Rect {
+                right: Val::Px(40.0),
+                bottom: Val::Px(60.0),
+                ..Default::default()
+            },
+            100.0,
+        ),
+    ];
+    for (actions, position, size) in buttons.iter() {
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: *position,
+                    size: Size::new(Val::Px(*size), Val::Px(*size)),
+                    ..Default::default()
+                },
+                material: material.clone(),
+                ..Default::default()
+            })
+            .with(TouchButton(actions));
+    }
+}
@@ -1,26 +1,14 @@
-use bevy::math::{Vec2, Vec3};
-
-use crate::constants::FLOOR_LAYER;
+use bevy::math::Vec2;
 
 pub const SCALE: f32 = 3.0;
 pub const TILE_WIDTH: f32 = 16.0 * SCALE;
 pub const HALF_TILE_WIDTH: f32 = 8.0 * SCALE;
 pub const FIX_DISTANCE: f32 = 10.0;
 
-pub fn get_way_translation(player_position: Vec2) -> Option<(Vec3, Vec3)> {
-    match player_position {
-        Vec2 { x, y } if x % TILE_WIDTH == 0.0 && y % TILE_WIDTH == 0.0 => None,
-        Vec2 { x, y } if x % TILE_WIDTH == 0.0 => {
-            let one = Vec3::new(x, (y / TILE_WIDTH).floor() * TILE_WIDTH, FLOOR_LAYER);
-            let two = Vec3::new(one.x, one.y + TILE_WIDTH, one.z);
-            Some((one, two))
-        }
-        Vec2 { x, y } => {
-            let one = Vec3::new((x / TILE_WIDTH).floor() * TILE_WIDTH, y, FLOOR_LAYER);
-            let two = Vec3::new(one.x + TILE_WIDTH, one.y, one.z);
-            Some((one, two))
-        }
-    }
+// The two lane centres either side of `coordinate`, along one axis.
+pub fn lanes_around(coordinate: f32) -> (f32, f32) {
+    let below = (coordinate / TILE_WIDTH).floor() * TILE_WIDTH;
+    (below, below + TILE_WIDTH)
 }
 pub fn vecs_xy_intersect(first: &Vec2, second: &Vec2) -> bool {
     first.x + TILE_WIDTH - FIX_DISTANCE > second.x
@@ -28,6 +16,11 @@ pub fn vecs_xy_intersect(first: &Vec2, second: &Vec2) -> bool {
         && first.y + TILE_WIDTH - FIX_DISTANCE > second.y
         && second.y + TILE_WIDTH - FIX_DISTANCE > first.y
 }
+// Same as vecs_xy_intersect, but `second` is `size` tiles wide.
+pub fn vecs_xy_intersect_sized(first: &Vec2, second: &Vec2, size: f32) -> bool {
+    let reach = TILE_WIDTH * (1.0 + size) / 2.0 - FIX_DISTANCE;
+    (first.x - second.x).abs() < reach && (first.y - second.y).abs() < reach
+}
 #[inline(always)]
 pub fn index_to_position(x: usize, y: usize, len: usize) -> Vec2 {
     Vec2::new(TILE_WIDTH * x as f32, TILE_WIDTH * (len - y - 1) as f32)